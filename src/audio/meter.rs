@@ -0,0 +1,196 @@
+use futures::stream::{self, Stream};
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, ChildStdout, Command};
+
+// Low rate mono is plenty for a meter and keeps the pipe cheap
+const SAMPLE_RATE: u32 = 16_000;
+// Bytes per level update: 50 ms of 16-bit samples
+const CHUNK_BYTES: usize = (SAMPLE_RATE as usize / 20) * 2;
+// Quietest level the meter shows, in dBFS
+const FLOOR_DB: f32 = -60.0;
+
+/// Signal level of one buffer, as linear amplitudes between 0 and 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl Level {
+    /// Position of `rms` on a meter running from -60 dBFS to 0 dBFS.
+    pub fn meter(&self) -> f32 {
+        if self.rms <= 0.0 {
+            return 0.0;
+        }
+        let db = 20.0 * self.rms.log10();
+        ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+    }
+
+    /// Whether the loudest sample hit full scale.
+    pub fn is_clipping(&self) -> bool {
+        self.peak >= 0.99
+    }
+}
+
+/// Peak and RMS of signed 16-bit little endian PCM. A trailing odd byte is
+/// ignored.
+pub fn measure(pcm: &[u8]) -> Level {
+    let samples = pcm
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0);
+
+    let (mut peak, mut sum, mut count) = (0.0f32, 0.0f32, 0usize);
+    for sample in samples {
+        peak = peak.max(sample.abs());
+        sum += sample * sample;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Level::default();
+    }
+    Level {
+        peak,
+        rms: (sum / count as f32).sqrt(),
+    }
+}
+
+/// Levels of `device`, or of the default source when unset, roughly 20 times
+/// a second. Ends when neither `parec` nor `pw-record` can be started.
+///
+/// The capture process is killed when the stream is dropped.
+pub fn monitor(device: Option<String>) -> impl Stream<Item = Level> {
+    stream::unfold(None, move |capture: Option<(Child, ChildStdout)>| {
+        let device = device.clone();
+        async move {
+            let (child, mut stdout) = match capture {
+                Some(capture) => capture,
+                None => spawn_capture(device.as_deref())?,
+            };
+
+            let mut buffer = vec![0; CHUNK_BYTES];
+            stdout.read_exact(&mut buffer).await.ok()?;
+            Some((measure(&buffer), Some((child, stdout))))
+        }
+    })
+}
+
+// Raw mono s16le capture from parec, or pw-record where PulseAudio tools are missing
+fn spawn_capture(device: Option<&str>) -> Option<(Child, ChildStdout)> {
+    let rate = SAMPLE_RATE.to_string();
+    let mut command = if which::which("parec").is_ok() {
+        let mut command = Command::new("parec");
+        command.args([
+            "--raw",
+            "--format=s16le",
+            "--channels=1",
+            "--latency-msec=50",
+            &format!("--rate={rate}"),
+        ]);
+        if let Some(device) = device {
+            command.arg(format!("--device={device}"));
+        }
+        command
+    } else {
+        let mut command = Command::new("pw-record");
+        command.args([
+            "--raw",
+            "--format",
+            "s16",
+            "--channels",
+            "1",
+            "--rate",
+            &rate,
+        ]);
+        if let Some(device) = device {
+            command.args(["--target", device]);
+        }
+        command.arg("-");
+        command
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    Some((child, stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcm(samples: impl IntoIterator<Item = i16>) -> Vec<u8> {
+        samples.into_iter().flat_map(i16::to_le_bytes).collect()
+    }
+
+    // 50 ms of a 1 kHz sine, as the meter reads it
+    fn sine(amplitude: f32) -> Vec<u8> {
+        pcm((0..CHUNK_BYTES / 2).map(|i| {
+            let phase = i as f32 * 1000.0 / SAMPLE_RATE as f32 * std::f32::consts::TAU;
+            (phase.sin() * amplitude * i16::MAX as f32).round() as i16
+        }))
+    }
+
+    #[test]
+    fn silence() {
+        let level = measure(&pcm([0; 800]));
+        assert_eq!(level, Level::default());
+        assert_eq!(level.meter(), 0.0);
+        assert!(!level.is_clipping());
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let level = measure(&sine(1.0));
+        assert!((level.peak - 1.0).abs() < 0.001, "{level:?}");
+        // A sine's RMS is its amplitude over √2, about -3 dBFS
+        assert!(
+            (level.rms - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001,
+            "{level:?}"
+        );
+        assert!((level.meter() - 0.95).abs() < 0.001, "{level:?}");
+        assert!(level.is_clipping());
+    }
+
+    #[test]
+    fn quiet_sine() {
+        // -40 dBFS peak, -43 dBFS RMS
+        let level = measure(&sine(0.01));
+        assert!((level.peak - 0.01).abs() < 0.001, "{level:?}");
+        assert!((level.meter() - 17.0 / 60.0).abs() < 0.01, "{level:?}");
+        assert!(!level.is_clipping());
+    }
+
+    #[test]
+    fn clipping() {
+        // Squared off at both rails, i16::MIN being one step past i16::MAX
+        let level = measure(&pcm([i16::MAX, i16::MIN, i16::MAX, i16::MIN]));
+        assert_eq!(level.peak, 1.0);
+        assert!(level.rms > 0.99);
+        assert!(level.meter() > 0.999);
+        assert!(level.is_clipping());
+    }
+
+    #[test]
+    fn empty_and_odd_buffers() {
+        assert_eq!(measure(&[]), Level::default());
+        assert_eq!(measure(&[0x7f]), Level::default());
+
+        // The trailing byte would be a loud half sample if it were read
+        let mut odd = pcm([16384, -16384]);
+        odd.push(0x7f);
+        assert_eq!(
+            measure(&odd),
+            Level {
+                peak: 0.5,
+                rms: 0.5
+            }
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use anyhow::{Context, Result};
+use std::process::Command;
+
+/// Prefix of the null sink the mix is played into. The sink is named after
+/// the process that created it, e.g. `wf_recorder_mix_4242`, so leftovers of
+/// a dead process can be told apart from the mix of one still recording.
+pub const MIX_SINK: &str = "wf_recorder_mix";
+
+/// Loading and unloading of sound server modules.
+pub trait ModuleLoader: Send {
+    /// Loads `module` and returns its index.
+    fn load(&self, module: &str, args: &[String]) -> Result<u32>;
+    fn unload(&self, index: u32) -> Result<()>;
+    /// Loaded modules as `(index, name, arguments)`.
+    fn list(&self) -> Result<Vec<(u32, String, String)>>;
+}
+
+/// `ModuleLoader` backed by the `pactl` command, which talks to PulseAudio
+/// as well as pipewire-pulse.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pactl;
+
+impl Pactl {
+    fn run(args: &[&str]) -> Result<String> {
+        let output = Command::new("pactl")
+            .args(args)
+            .env("LC_ALL", "C")
+            .output()
+            .context("Failed to run pactl")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "pactl {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl ModuleLoader for Pactl {
+    fn load(&self, module: &str, args: &[String]) -> Result<u32> {
+        let mut command = vec!["load-module", module];
+        command.extend(args.iter().map(String::as_str));
+        let index = Self::run(&command)?;
+        index
+            .trim()
+            .parse()
+            .with_context(|| format!("Unexpected module index from pactl: {}", index.trim()))
+    }
+
+    fn unload(&self, index: u32) -> Result<()> {
+        Self::run(&["unload-module", &index.to_string()]).map(|_| ())
+    }
+
+    fn list(&self) -> Result<Vec<(u32, String, String)>> {
+        Ok(parse_modules(&Self::run(&["list", "modules", "short"])?))
+    }
+}
+
+/// Parses `pactl list modules short`.
+pub fn parse_modules(text: &str) -> Vec<(u32, String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let index = fields.next()?.trim().parse().ok()?;
+            let name = fields.next()?.to_string();
+            let args = fields.next().unwrap_or_default().to_string();
+            Some((index, name, args))
+        })
+        .collect()
+}
+
+/// A null sink fed by loopbacks of a monitor and a microphone, so both can
+/// be recorded from its monitor as a single source.
+///
+/// The modules are unloaded on drop. Modules left behind by a process that
+/// was killed are removed by `remove_leftovers`.
+pub struct AudioMix<L: ModuleLoader = Pactl> {
+    loader: L,
+    sink: String,
+    modules: Vec<u32>,
+}
+
+impl<L: ModuleLoader> AudioMix<L> {
+    pub fn create(loader: L, monitor: &str, microphone: &str) -> Result<Self> {
+        remove_leftovers(&loader);
+
+        // Unloads whatever was loaded so far if a later step fails
+        let mut mix = Self {
+            loader,
+            sink: sink_name(std::process::id()),
+            modules: Vec::new(),
+        };
+
+        let sink = mix.loader.load(
+            "module-null-sink",
+            &[
+                format!("sink_name={}", mix.sink),
+                String::from("sink_properties=device.description=WF-Recorder-Mix"),
+            ],
+        )?;
+        mix.modules.push(sink);
+
+        for source in [monitor, microphone] {
+            let loopback = mix
+                .loader
+                .load(
+                    "module-loopback",
+                    &[
+                        format!("source={source}"),
+                        format!("sink={}", mix.sink),
+                        String::from("latency_msec=20"),
+                    ],
+                )
+                .with_context(|| format!("Failed to route {source} into the mix"))?;
+            mix.modules.push(loopback);
+        }
+
+        Ok(mix)
+    }
+
+    /// Source to hand to wf-recorder.
+    pub fn source(&self) -> String {
+        format!("{}.monitor", self.sink)
+    }
+}
+
+impl<L: ModuleLoader> Drop for AudioMix<L> {
+    fn drop(&mut self) {
+        // Loopbacks first, so nothing plays into a sink that is going away
+        for index in self.modules.drain(..).rev() {
+            if let Err(e) = self.loader.unload(index) {
+                eprintln!("Failed to unload audio module {index}: {e}");
+            }
+        }
+    }
+}
+
+/// Unloads modules from a mix whose process died before tearing it down.
+/// Mixes of processes that are still running are left alone.
+pub fn remove_leftovers(loader: &impl ModuleLoader) {
+    let Ok(modules) = loader.list() else {
+        return;
+    };
+
+    // Loopbacks reference the sink too, unload them before it
+    let mut leftovers: Vec<_> = modules
+        .into_iter()
+        .filter(|(_, _, args)| mix_owner(args).is_some_and(|pid| !is_running(pid)))
+        .collect();
+    leftovers.sort_by_key(|(_, name, _)| name == "module-null-sink");
+
+    for (index, _, _) in leftovers {
+        let _ = loader.unload(index);
+    }
+}
+
+fn sink_name(pid: u32) -> String {
+    format!("{MIX_SINK}_{pid}")
+}
+
+// The process a mix module was loaded for, from the sink named in its arguments
+fn mix_owner(args: &str) -> Option<u32> {
+    args.split_whitespace()
+        .filter_map(|arg| arg.split_once('=').map(|(_, value)| value))
+        .find_map(|sink| sink.strip_prefix(MIX_SINK)?.strip_prefix('_')?.parse().ok())
+}
+
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks whether the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists but belongs to someone else
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Call {
+        Load(String, Vec<String>),
+        Unload(u32),
+    }
+
+    // Hands out indices from 10 on and records every call; `fail_on` makes
+    // the nth load fail
+    #[derive(Default, Clone)]
+    struct FakeLoader {
+        calls: Arc<Mutex<Vec<Call>>>,
+        modules: Vec<(u32, String, String)>,
+        fail_on: Option<usize>,
+    }
+
+    impl FakeLoader {
+        fn calls(&self) -> Vec<Call> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl ModuleLoader for FakeLoader {
+        fn load(&self, module: &str, args: &[String]) -> Result<u32> {
+            let mut calls = self.calls.lock().unwrap();
+            let loads = calls
+                .iter()
+                .filter(|call| matches!(call, Call::Load(..)))
+                .count();
+            if self.fail_on == Some(loads) {
+                anyhow::bail!("No such entity");
+            }
+            calls.push(Call::Load(module.to_string(), args.to_vec()));
+            Ok(10 + loads as u32)
+        }
+
+        fn unload(&self, index: u32) -> Result<()> {
+            self.calls.lock().unwrap().push(Call::Unload(index));
+            Ok(())
+        }
+
+        fn list(&self) -> Result<Vec<(u32, String, String)>> {
+            Ok(self.modules.clone())
+        }
+    }
+
+    fn unloads(calls: &[Call]) -> Vec<u32> {
+        calls
+            .iter()
+            .filter_map(|call| match call {
+                Call::Unload(index) => Some(*index),
+                Call::Load(..) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mix_is_loaded_and_unloaded_in_reverse() {
+        let loader = FakeLoader::default();
+        let sink = sink_name(std::process::id());
+        let mix = AudioMix::create(loader.clone(), "speakers.monitor", "mic").unwrap();
+        assert_eq!(mix.source(), format!("{sink}.monitor"));
+
+        let loopback = |source: &str| {
+            Call::Load(
+                String::from("module-loopback"),
+                vec![
+                    format!("source={source}"),
+                    format!("sink={sink}"),
+                    String::from("latency_msec=20"),
+                ],
+            )
+        };
+        assert_eq!(
+            loader.calls(),
+            [
+                Call::Load(
+                    String::from("module-null-sink"),
+                    vec![
+                        format!("sink_name={sink}"),
+                        String::from("sink_properties=device.description=WF-Recorder-Mix"),
+                    ]
+                ),
+                loopback("speakers.monitor"),
+                loopback("mic"),
+            ]
+        );
+
+        drop(mix);
+        assert_eq!(unloads(&loader.calls()), [12, 11, 10]);
+    }
+
+    #[test]
+    fn failed_mix_unloads_what_was_loaded() {
+        let loader = FakeLoader {
+            fail_on: Some(2),
+            ..FakeLoader::default()
+        };
+        let error = AudioMix::create(loader.clone(), "speakers.monitor", "mic")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Failed to route mic into the mix");
+        assert_eq!(unloads(&loader.calls()), [11, 10]);
+    }
+
+    #[test]
+    fn only_leftovers_of_dead_processes_are_removed() {
+        // No process can have this pid, it is above the kernel's limit
+        let dead = 999_999_999;
+        let alive = std::process::id();
+        let module = |index: u32, name: &str, args: String| (index, name.to_string(), args);
+        let loader = FakeLoader {
+            modules: vec![
+                module(
+                    3,
+                    "module-null-sink",
+                    format!("sink_name={}", sink_name(dead)),
+                ),
+                module(
+                    4,
+                    "module-loopback",
+                    format!(
+                        "source=speakers.monitor sink={} latency_msec=20",
+                        sink_name(dead)
+                    ),
+                ),
+                // Another instance recording right now
+                module(
+                    5,
+                    "module-null-sink",
+                    format!("sink_name={}", sink_name(alive)),
+                ),
+                module(
+                    6,
+                    "module-loopback",
+                    format!("source=mic sink={} latency_msec=20", sink_name(alive)),
+                ),
+                // Someone else's modules, even with a similar name
+                module(7, "module-null-sink", format!("sink_name={MIX_SINK}_extra")),
+                module(8, "module-alsa-card", String::from("device_id=\"0\"")),
+            ],
+            ..FakeLoader::default()
+        };
+
+        remove_leftovers(&loader);
+        assert_eq!(loader.calls(), [Call::Unload(4), Call::Unload(3)]);
+    }
+
+    #[test]
+    fn module_list() {
+        let text = "6\tmodule-always-sink\t\n\
+                    536870912\tmodule-null-sink\tsink_name=wf_recorder_mix_4242 sink_properties=device.description=WF-Recorder-Mix\n\
+                    536870913\tmodule-loopback\tsource=mic sink=wf_recorder_mix_4242 latency_msec=20\n";
+        let modules = parse_modules(text);
+        assert_eq!(modules.len(), 3);
+        assert_eq!(
+            modules[0],
+            (6, String::from("module-always-sink"), String::new())
+        );
+        assert_eq!(mix_owner(&modules[0].2), None);
+        assert_eq!(mix_owner(&modules[1].2), Some(4242));
+        assert_eq!(mix_owner(&modules[2].2), Some(4242));
+    }
+}
@@ -1 +1,354 @@
-// This module is intentionally empty as audio handling is done directly in the recorder implementation
+mod meter;
+mod mix;
+
+use std::process::Command;
+
+use crate::recorder::AudioSource;
+
+pub use meter::{monitor, Level};
+pub use mix::{remove_leftovers, AudioMix, Pactl};
+
+/// A PulseAudio source. PipeWire lists the same devices through pipewire-pulse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioDevice {
+    /// Name wf-recorder expects for `--audio`, e.g. `alsa_input.pci-0000_00_1f.3.analog-stereo`
+    pub name: String,
+    pub description: String,
+    /// Whether this source captures what a sink plays rather than an input
+    pub monitor: bool,
+}
+
+/// The sources known to the sound server and its defaults.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    pub devices: Vec<AudioDevice>,
+    pub default_source: Option<String>,
+    pub default_sink: Option<String>,
+}
+
+impl Sources {
+    /// Asks `pactl` for the current sources. Empty when it is unavailable.
+    pub fn query() -> Self {
+        let Some(list) = pactl(&["list", "sources"]) else {
+            return Self::default();
+        };
+        let (default_source, default_sink) = pactl(&["info"])
+            .map(|info| parse_defaults(&info))
+            .unwrap_or_default();
+
+        Self {
+            devices: parse_sources(&list),
+            default_source,
+            default_sink,
+        }
+    }
+
+    /// Devices that make sense for `source`: monitors for system audio,
+    /// inputs for the microphone.
+    pub fn devices_for(&self, source: AudioSource) -> Vec<&AudioDevice> {
+        let monitor = match source {
+            AudioSource::None | AudioSource::Both => return Vec::new(),
+            AudioSource::System => true,
+            AudioSource::Microphone => false,
+        };
+        self.devices
+            .iter()
+            .filter(|device| device.monitor == monitor)
+            .collect()
+    }
+
+    /// Device to record `source` from.
+    ///
+    /// `preferred` wins while it still exists, then the sound server's default
+    /// input or the monitor of its default output, then the first match.
+    /// `None` leaves the choice to wf-recorder. A mix is resolved one device
+    /// at a time, so `Both` never resolves.
+    pub fn resolve(&self, source: AudioSource, preferred: Option<&str>) -> Option<String> {
+        if matches!(source, AudioSource::None | AudioSource::Both) {
+            return None;
+        }
+
+        // Nothing to check against, trust the saved choice
+        if self.devices.is_empty() {
+            return preferred.map(String::from);
+        }
+
+        let candidates = self.devices_for(source);
+        let default = match source {
+            AudioSource::System => self
+                .default_sink
+                .as_ref()
+                .map(|sink| format!("{sink}.monitor")),
+            _ => self.default_source.clone(),
+        };
+
+        [preferred.map(String::from), default]
+            .into_iter()
+            .flatten()
+            .find(|name| candidates.iter().any(|device| device.name == *name))
+            .or_else(|| candidates.first().map(|device| device.name.clone()))
+    }
+}
+
+// Runs pactl with untranslated output, since the parsers match on English labels
+fn pactl(args: &[&str]) -> Option<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses `pactl list sources`.
+pub fn parse_sources(text: &str) -> Vec<AudioDevice> {
+    let mut devices = Vec::new();
+    let mut current: Option<AudioDevice> = None;
+
+    for line in text.lines() {
+        if is_section_header(line) {
+            devices.extend(current.take());
+            current = Some(AudioDevice {
+                name: String::new(),
+                description: String::new(),
+                monitor: false,
+            });
+            continue;
+        }
+
+        // Properties are nested one level deeper and use `key = value`
+        let (Some(device), Some(field)) = (current.as_mut(), line.strip_prefix('\t')) else {
+            continue;
+        };
+        if let Some(property) = field.strip_prefix('\t') {
+            // Property keys are never translated, unlike the labels above them
+            match property.split_once(" = ") {
+                Some(("device.class", class)) => device.monitor |= class == "\"monitor\"",
+                Some(("device.description", description)) if device.description.is_empty() => {
+                    device.description = description.trim_matches('"').to_string();
+                }
+                _ => {}
+            }
+        } else if let Some(name) = field.strip_prefix("Name: ") {
+            device.name = name.trim().to_string();
+        } else if let Some(description) = field.strip_prefix("Description: ") {
+            device.description = description.trim().to_string();
+        } else if let Some(sink) = field.strip_prefix("Monitor of Sink: ") {
+            device.monitor = sink.trim() != "n/a";
+        }
+    }
+    devices.extend(current);
+
+    devices.retain(|device| !device.name.is_empty());
+    devices
+}
+
+// `Source #52`, or `Quelle #52` and the like when pactl speaks another language
+fn is_section_header(line: &str) -> bool {
+    !line.starts_with(char::is_whitespace)
+        && line.rsplit_once(" #").is_some_and(|(_, index)| {
+            !index.is_empty() && index.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// Parses the default source and sink out of `pactl info`.
+pub fn parse_defaults(text: &str) -> (Option<String>, Option<String>) {
+    let value = |label: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(label))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    (value("Default Source:"), value("Default Sink:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `pactl list sources` from pipewire-pulse, trimmed to two sources
+    const SOURCES: &str = "Source #52
+\tState: SUSPENDED
+\tName: alsa_output.pci-0000_00_1f.3.analog-stereo.monitor
+\tDescription: Monitor of Built-in Audio Analog Stereo
+\tDriver: PipeWire
+\tSample Specification: s32le 2ch 48000Hz
+\tChannel Map: front-left,front-right
+\tOwner Module: 4294967295
+\tMute: no
+\tVolume: front-left: 65536 / 100% / 0.00 dB,   front-right: 65536 / 100% / 0.00 dB
+\t        balance 0.00
+\tBase Volume: 65536 / 100% / 0.00 dB
+\tMonitor of Sink: alsa_output.pci-0000_00_1f.3.analog-stereo
+\tLatency: 0 usec, configured 0 usec
+\tFlags: HARDWARE DECIBEL_VOLUME LATENCY 
+\tProperties:
+\t\tobject.serial = \"53\"
+\t\tdevice.description = \"Built-in Audio Analog Stereo\"
+\t\tdevice.class = \"monitor\"
+\t\tnode.name = \"alsa_output.pci-0000_00_1f.3.analog-stereo\"
+\tFormats:
+\t\tpcm
+
+Source #53
+\tState: RUNNING
+\tName: alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo
+\tDescription: Yeti Stereo Microphone Analog Stereo
+\tDriver: PipeWire
+\tSample Specification: s16le 2ch 48000Hz
+\tChannel Map: front-left,front-right
+\tOwner Module: 4294967295
+\tMute: no
+\tVolume: front-left: 52429 / 80% / -5.81 dB,   front-right: 52429 / 80% / -5.81 dB
+\t        balance 0.00
+\tBase Volume: 65536 / 100% / 0.00 dB
+\tMonitor of Sink: n/a
+\tLatency: 0 usec, configured 0 usec
+\tFlags: HARDWARE HW_MUTE_CTRL HW_VOLUME_CTRL DECIBEL_VOLUME LATENCY 
+\tProperties:
+\t\tdevice.description = \"Yeti Stereo Microphone\"
+\t\tdevice.class = \"sound\"
+\t\tnode.name = \"alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo\"
+\tPorts:
+\t\tanalog-input-mic: Microphone (type: Mic, priority: 8700, availability unknown)
+\tActive Port: analog-input-mic
+\tFormats:
+\t\tpcm
+";
+
+    // The same monitor with LANG=de_DE.UTF-8, in case LC_ALL is not honoured
+    const SOURCES_DE: &str = "Quelle #52
+\tStatus: SUSPENDED
+\tName: alsa_output.pci-0000_00_1f.3.analog-stereo.monitor
+\tBeschreibung: Monitor of Built-in Audio Analog Stereo
+\tTreiber: PipeWire
+\tMonitor der Senke: alsa_output.pci-0000_00_1f.3.analog-stereo
+\tEigenschaften:
+\t\tdevice.description = \"Built-in Audio Analog Stereo\"
+\t\tdevice.class = \"monitor\"
+";
+
+    const INFO: &str = "Server String: /run/user/1000/pulse/native
+Library Protocol Version: 35
+Server Protocol Version: 35
+Is Local: yes
+Client Index: 118
+Tile Size: 65472
+User Name: alice
+Host Name: workstation
+Server Name: PulseAudio (on PipeWire 1.0.5)
+Server Version: 15.0.0
+Default Sample Specification: float32le 2ch 48000Hz
+Default Channel Map: front-left,front-right
+Default Sink: alsa_output.pci-0000_00_1f.3.analog-stereo
+Default Source: alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo
+Cookie: 9c1b:0e4d
+";
+
+    fn device(name: &str, description: &str, monitor: bool) -> AudioDevice {
+        AudioDevice {
+            name: name.to_string(),
+            description: description.to_string(),
+            monitor,
+        }
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(
+            parse_sources(SOURCES),
+            [
+                device(
+                    "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor",
+                    "Monitor of Built-in Audio Analog Stereo",
+                    true
+                ),
+                device(
+                    "alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo",
+                    "Yeti Stereo Microphone Analog Stereo",
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn translated_sources() {
+        // Labels are translated, so the description and the monitor flag come
+        // from the properties
+        assert_eq!(
+            parse_sources(SOURCES_DE),
+            [device(
+                "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor",
+                "Built-in Audio Analog Stereo",
+                true
+            )]
+        );
+    }
+
+    #[test]
+    fn no_sources() {
+        assert_eq!(parse_sources(""), []);
+        assert_eq!(
+            parse_sources("Connection failure: Connection refused\n"),
+            []
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            parse_defaults(INFO),
+            (
+                Some(String::from(
+                    "alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo"
+                )),
+                Some(String::from("alsa_output.pci-0000_00_1f.3.analog-stereo"))
+            )
+        );
+    }
+
+    #[test]
+    fn missing_defaults() {
+        // A server without any sink reports no default source or sink at all
+        let info = INFO
+            .lines()
+            .filter(|line| !line.starts_with("Default S"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(parse_defaults(&info), (None, None));
+        assert_eq!(parse_defaults("Default Sink: \n"), (None, None));
+    }
+
+    #[test]
+    fn resolve_falls_back_to_defaults() {
+        let sources = Sources {
+            devices: parse_sources(SOURCES),
+            ..Sources::default()
+        };
+        let (default_source, default_sink) = parse_defaults(INFO);
+        let with_defaults = Sources {
+            default_source,
+            default_sink,
+            ..sources.clone()
+        };
+
+        assert_eq!(
+            with_defaults.resolve(AudioSource::System, Some("gone.monitor")),
+            Some(String::from(
+                "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"
+            ))
+        );
+        // Without defaults the first matching device is taken
+        assert_eq!(
+            sources.resolve(AudioSource::Microphone, None),
+            Some(String::from(
+                "alsa_input.usb-Blue_Microphones_Yeti_Stereo_Microphone_REV8-00.analog-stereo"
+            ))
+        );
+        assert_eq!(sources.resolve(AudioSource::Both, None), None);
+    }
+}
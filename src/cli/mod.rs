@@ -0,0 +1,859 @@
+mod state;
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::Config;
+use crate::control::{self, Request};
+use crate::diagnostics;
+use crate::instance::{self, Holder, InstanceLock, Owner};
+use crate::recorder::{
+    preflight, AudioSource, CaptureRegion, DiskSpace, EncoderBackend, Framerate, OutputFormat,
+    QualityPreset, Recorder, RecorderEvent, VideoCodec,
+};
+pub use state::RecordingState;
+
+/// Exit codes of the command line interface.
+pub mod exit {
+    pub const OK: i32 = 0;
+    /// The recording failed, or something kept it from starting
+    pub const FAILURE: i32 = 1;
+    /// The arguments could not be understood
+    pub const USAGE: i32 = 2;
+    /// `stop` or `status` found no recording
+    pub const NOT_RECORDING: i32 = 3;
+    /// `start` found a recording already running
+    pub const ALREADY_RECORDING: i32 = 4;
+}
+
+// How long `stop` waits for the recording process to save its file
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str = "\
+Usage: wf-recorder-gui [COMMAND] [OPTIONS]
+
+Without a command the window is opened.
+
+Commands:
+  start     Record until stopped, without a window
+  stop      Stop the recording started with `start`
+  status    Print the recording state as JSON
+  toggle    Stop the running recording, or start one
+            While the window is open, these are passed on to it instead
+  --doctor  Check the tools and the session, see the Diagnostics view
+  --help    Show this help
+
+Options for start and toggle override the saved settings for one recording:
+  --format <webm|mp4|mkv>
+  --codec <h264|h265|vp8|vp9|av1-svt|av1-aom|ffv1>
+  --quality <draft|balanced|high|lossless|custom>
+                              custom uses the saved custom quality settings
+  --encoder <software|vaapi|nvenc>
+  --framerate <fps>
+  --audio <none|system|mic|both>
+  --region <full|select>
+  --monitor <name>            Record one monitor, e.g. DP-1
+  --output-dir <dir>
+  --filename <template>
+  --max-duration <minutes>
+  --max-size <megabytes>
+  --split <minutes>
+
+Exit codes: 0 success, 1 failure, 2 bad arguments, 3 not recording,
+4 already recording. `status` exits with 0 while recording and 3 otherwise.
+";
+
+/// The files through which the command line finds the recording or window
+/// it talks to.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    /// See `RecordingState::path`
+    pub state: PathBuf,
+    /// See `InstanceLock::path`
+    pub lock: PathBuf,
+    /// See `control::socket_path`
+    pub socket: PathBuf,
+}
+
+impl Paths {
+    /// The files every instance uses, in the runtime dir.
+    pub fn runtime() -> Self {
+        Self {
+            state: RecordingState::path(),
+            lock: InstanceLock::path(),
+            socket: control::socket_path(),
+        }
+    }
+
+    fn load(&self) -> Option<RecordingState> {
+        RecordingState::load(&self.state, &self.lock)
+    }
+}
+
+/// What the command line asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliCommand {
+    /// Open the window
+    Gui,
+    Doctor,
+    Help,
+    Start(Vec<Override>),
+    Stop,
+    Status,
+    Toggle(Vec<Override>),
+}
+
+/// A setting given on the command line, applied on top of the saved ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Override {
+    Format(OutputFormat),
+    Codec(VideoCodec),
+    Quality(QualityPreset),
+    Encoder(EncoderBackend),
+    Framerate(Framerate),
+    Audio(AudioSource),
+    Region(CaptureRegion),
+    OutputDir(PathBuf),
+    Filename(String),
+    MaxDuration(u32),
+    MaxSize(u64),
+    Split(u32),
+}
+
+impl Override {
+    fn apply(&self, config: &mut Config) {
+        match self.clone() {
+            Override::Format(format) => {
+                config.audio_settings = config.audio_settings.switch_format(config.format, format);
+                config.format = format;
+            }
+            Override::Codec(codec) => config.codec = codec,
+            Override::Quality(quality) => config.quality = quality,
+            Override::Encoder(backend) => config.encoder_backend = backend,
+            Override::Framerate(framerate) => config.framerate = framerate,
+            Override::Audio(audio) => config.audio = audio,
+            Override::Region(region) => config.region = region,
+            Override::OutputDir(dir) => config.output_dir = dir,
+            Override::Filename(template) => config.filename_template = template,
+            Override::MaxDuration(minutes) => config.max_duration_minutes = Some(minutes),
+            Override::MaxSize(megabytes) => config.max_file_size_mb = Some(megabytes),
+            Override::Split(minutes) => config.split_minutes = Some(minutes),
+        }
+    }
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<CliCommand> {
+    let Some((command, rest)) = args.split_first() else {
+        return Ok(CliCommand::Gui);
+    };
+
+    let command = match command.as_str() {
+        "start" => CliCommand::Start(parse_overrides(rest)?),
+        "toggle" => CliCommand::Toggle(parse_overrides(rest)?),
+        "stop" | "status" | "--doctor" | "--help" | "-h" if !rest.is_empty() => {
+            anyhow::bail!("{command} takes no options");
+        }
+        "stop" => CliCommand::Stop,
+        "status" => CliCommand::Status,
+        "--doctor" => CliCommand::Doctor,
+        "--help" | "-h" => CliCommand::Help,
+        other => anyhow::bail!("Unknown command {other}"),
+    };
+    Ok(command)
+}
+
+fn parse_overrides(args: &[String]) -> Result<Vec<Override>> {
+    let mut overrides = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(flag) = args.next() {
+        // Both `--flag value` and `--flag=value` are accepted
+        let (flag, value) = match flag.split_once('=') {
+            Some((flag, value)) => (flag, value.to_string()),
+            None => (
+                flag.as_str(),
+                args.next()
+                    .with_context(|| format!("{flag} needs a value"))?
+                    .clone(),
+            ),
+        };
+
+        let value = value.as_str();
+        overrides.push(match flag {
+            "--format" => Override::Format(choice(
+                flag,
+                value,
+                &[
+                    ("webm", OutputFormat::WebM),
+                    ("mp4", OutputFormat::Mp4),
+                    ("mkv", OutputFormat::Mkv),
+                ],
+            )?),
+            "--codec" => Override::Codec(choice(
+                flag,
+                value,
+                &[
+                    ("h264", VideoCodec::H264),
+                    ("h265", VideoCodec::H265),
+                    ("vp8", VideoCodec::Vp8),
+                    ("vp9", VideoCodec::Vp9),
+                    ("av1-svt", VideoCodec::Av1Svt),
+                    ("av1-aom", VideoCodec::Av1Aom),
+                    ("ffv1", VideoCodec::Ffv1),
+                ],
+            )?),
+            "--quality" => Override::Quality(choice(
+                flag,
+                value,
+                &[
+                    ("draft", QualityPreset::Draft),
+                    ("balanced", QualityPreset::Balanced),
+                    ("high", QualityPreset::High),
+                    ("lossless", QualityPreset::Lossless),
+                    ("custom", QualityPreset::Custom),
+                ],
+            )?),
+            "--encoder" => Override::Encoder(choice(
+                flag,
+                value,
+                &[
+                    ("software", EncoderBackend::Software),
+                    ("vaapi", EncoderBackend::Vaapi),
+                    ("nvenc", EncoderBackend::Nvenc),
+                ],
+            )?),
+            "--framerate" => Override::Framerate(
+                number(flag, value)
+                    .ok()
+                    .and_then(Framerate::new)
+                    .with_context(|| {
+                        format!(
+                            "{flag} has to be between {} and {}",
+                            Framerate::MIN,
+                            Framerate::MAX
+                        )
+                    })?,
+            ),
+            "--audio" => Override::Audio(choice(
+                flag,
+                value,
+                &[
+                    ("none", AudioSource::None),
+                    ("system", AudioSource::System),
+                    ("mic", AudioSource::Microphone),
+                    ("both", AudioSource::Both),
+                ],
+            )?),
+            "--region" => Override::Region(choice(
+                flag,
+                value,
+                &[
+                    ("full", CaptureRegion::FullScreen),
+                    ("select", CaptureRegion::Selection),
+                ],
+            )?),
+            "--monitor" => Override::Region(CaptureRegion::Output(value.to_string())),
+            "--output-dir" => Override::OutputDir(PathBuf::from(value)),
+            "--filename" => Override::Filename(value.to_string()),
+            "--max-duration" => Override::MaxDuration(number(flag, value)?),
+            "--max-size" => Override::MaxSize(number(flag, value)?),
+            "--split" => Override::Split(number(flag, value)?),
+            other => anyhow::bail!("Unknown option {other}"),
+        });
+    }
+
+    // Caught here rather than after the saved settings are read
+    let format = overrides.iter().rev().find_map(|setting| match setting {
+        Override::Format(format) => Some(*format),
+        _ => None,
+    });
+    let codec = overrides.iter().rev().find_map(|setting| match setting {
+        Override::Codec(codec) => Some(*codec),
+        _ => None,
+    });
+    if let (Some(format), Some(codec)) = (format, codec) {
+        if !format.supports(codec) {
+            anyhow::bail!(
+                "{} files cannot hold {}",
+                format.extension(),
+                codec.encoder()
+            );
+        }
+    }
+    Ok(overrides)
+}
+
+fn choice<T: Clone>(flag: &str, value: &str, choices: &[(&str, T)]) -> Result<T> {
+    choices
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, choice)| choice.clone())
+        .with_context(|| {
+            let names: Vec<&str> = choices.iter().map(|(name, _)| *name).collect();
+            format!("{flag} has to be one of {}", names.join(", "))
+        })
+}
+
+// A positive whole number
+fn number<T: std::str::FromStr + Default + PartialEq>(flag: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .ok()
+        .filter(|number| *number != T::default())
+        .with_context(|| format!("{flag} has to be a positive whole number"))
+}
+
+/// Runs `command` without a window and returns the exit code. `Gui` is left
+/// to the caller.
+pub fn run(command: CliCommand, paths: &Paths) -> i32 {
+    match command {
+        CliCommand::Gui => exit::OK,
+        CliCommand::Help => {
+            print!("{USAGE}");
+            exit::OK
+        }
+        CliCommand::Doctor => {
+            let checks = diagnostics::run(&Config::load().unwrap_or_default());
+            print!("{}", diagnostics::report(&checks));
+            if diagnostics::has_failures(&checks) {
+                exit::FAILURE
+            } else {
+                exit::OK
+            }
+        }
+        CliCommand::Status => status(paths),
+        CliCommand::Stop => stop(paths),
+        CliCommand::Start(overrides) => start(&overrides, paths),
+        CliCommand::Toggle(overrides) => {
+            if paths.load().is_some() {
+                return stop(paths);
+            }
+            let Some(window) = open_window(paths) else {
+                return start(&overrides, paths);
+            };
+            let idle = control::request(&paths.socket, &Request::Status)
+                .is_ok_and(|response| response["status"]["state"] == "idle");
+            if !idle {
+                forward(Request::Stop, paths)
+            } else if !overrides.is_empty() {
+                refuse_overrides(window)
+            } else {
+                forward(Request::Start, paths)
+            }
+        }
+    }
+}
+
+/// Deals with a second launch of the window: the open one is brought to the
+/// front, a headless recording is pointed out. Returns the exit code.
+pub fn already_running(paths: &Paths) -> i32 {
+    match instance::holder(&paths.lock) {
+        Some(Holder {
+            owner: Owner::Window,
+            pid,
+        }) => match control::request(&paths.socket, &Request::Focus) {
+            Ok(_) => {
+                eprintln!("wf-recorder-gui is already open (pid {pid}), switched to it");
+                exit::OK
+            }
+            Err(e) => {
+                eprintln!("wf-recorder-gui is already open (pid {pid}) but does not answer: {e:#}");
+                exit::FAILURE
+            }
+        },
+        Some(Holder {
+            owner: Owner::Recording,
+            pid,
+        }) => {
+            eprintln!(
+                "A recording started with `wf-recorder-gui start` is running (pid {pid}), \
+                 stop it with `wf-recorder-gui stop` first"
+            );
+            exit::ALREADY_RECORDING
+        }
+        // Between taking the lock and writing itself into it
+        None => {
+            eprintln!("wf-recorder-gui is already starting");
+            exit::FAILURE
+        }
+    }
+}
+
+// Pid of the open window, if any
+fn open_window(paths: &Paths) -> Option<u32> {
+    match instance::holder(&paths.lock) {
+        Some(Holder {
+            owner: Owner::Window,
+            pid,
+        }) => Some(pid),
+        _ => None,
+    }
+}
+
+// Sends `request` to the open window and prints its answer
+fn forward(request: Request, paths: &Paths) -> i32 {
+    let response = match control::request(&paths.socket, &request) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("The open window does not answer: {e:#}");
+            return exit::FAILURE;
+        }
+    };
+    println!("{response}");
+    if response["ok"] == true {
+        return exit::OK;
+    }
+
+    if let Some(error) = response["error"].as_str() {
+        eprintln!("{error}");
+    }
+    let idle = response["status"]["state"] == "idle";
+    match request {
+        Request::Stop if idle => exit::NOT_RECORDING,
+        Request::Start if !idle => exit::ALREADY_RECORDING,
+        _ => exit::FAILURE,
+    }
+}
+
+fn refuse_overrides(window: u32) -> i32 {
+    eprintln!(
+        "The window is open (pid {window}) and records with its own settings, \
+         close it to pass options"
+    );
+    exit::USAGE
+}
+
+/// Prints the usage after a parse error and returns the exit code for it.
+pub fn usage_error(error: &anyhow::Error) -> i32 {
+    eprintln!("{error:#}\n\n{USAGE}");
+    exit::USAGE
+}
+
+fn status(paths: &Paths) -> i32 {
+    let Some(state) = paths.load() else {
+        return window_status(paths);
+    };
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    println!(
+        "{}",
+        serde_json::json!({
+            "recording": true,
+            "pid": state.pid,
+            "output": state.output,
+            "part": state.part,
+            "started_at": state.started_at,
+            "elapsed_secs": now.saturating_sub(state.started_at),
+        })
+    );
+    exit::OK
+}
+
+// The open window's state, `{"recording":false}` without one
+fn window_status(paths: &Paths) -> i32 {
+    let status = open_window(paths)
+        .and_then(|_| control::request(&paths.socket, &Request::Status).ok())
+        .map(|mut response| response["status"].take());
+    let Some(status) = status.filter(|status| status["state"] != "idle") else {
+        println!("{}", serde_json::json!({ "recording": false }));
+        return exit::NOT_RECORDING;
+    };
+    println!(
+        "{}",
+        serde_json::json!({ "recording": true, "window": status })
+    );
+    exit::OK
+}
+
+fn stop(paths: &Paths) -> i32 {
+    let Some(state) = paths.load() else {
+        if open_window(paths).is_some() {
+            return forward(Request::Stop, paths);
+        }
+        eprintln!("Not recording");
+        return exit::NOT_RECORDING;
+    };
+
+    // The recording process saves its file on SIGINT, like after Ctrl+C
+    // SAFETY: kill(2) has no memory safety requirements
+    if unsafe { libc::kill(state.pid as libc::pid_t, libc::SIGINT) } != 0 {
+        eprintln!(
+            "Failed to stop the recording: {}",
+            std::io::Error::last_os_error()
+        );
+        return exit::FAILURE;
+    }
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while state.is_running(&paths.lock) {
+        if Instant::now() >= deadline {
+            eprintln!("The recording did not stop in time");
+            return exit::FAILURE;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    println!("{}", state.output.display());
+    exit::OK
+}
+
+fn start(overrides: &[Override], paths: &Paths) -> i32 {
+    if paths.load().is_some() {
+        eprintln!("Already recording, use `wf-recorder-gui stop` first");
+        return exit::ALREADY_RECORDING;
+    }
+
+    // Held until the recording is saved, so no window records alongside
+    let _lock = match InstanceLock::acquire(&paths.lock, Owner::Recording) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            return match open_window(paths) {
+                Some(window) if !overrides.is_empty() => refuse_overrides(window),
+                Some(_) => forward(Request::Start, paths),
+                None => {
+                    eprintln!("Already recording, use `wf-recorder-gui stop` first");
+                    exit::ALREADY_RECORDING
+                }
+            };
+        }
+        Err(e) => {
+            eprintln!("Not guarding against a second instance: {e:#}");
+            None
+        }
+    };
+
+    let mut config = Config::load().unwrap_or_default();
+    for setting in overrides {
+        setting.apply(&mut config);
+    }
+    // A saved codec gives way to a new format, one asked for does not
+    let codec_given = overrides
+        .iter()
+        .any(|setting| matches!(setting, Override::Codec(_)));
+    if !codec_given && !config.format.supports(config.codec) {
+        config.codec = config.format.default_codec();
+    }
+    if !config.format.supports(config.codec) {
+        eprintln!(
+            "{} files cannot hold {}",
+            config.format.extension(),
+            config.codec.encoder()
+        );
+        return exit::USAGE;
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start the async runtime: {e}");
+            return exit::FAILURE;
+        }
+    };
+    match runtime.block_on(record(config, &paths.state)) {
+        Ok(()) => exit::OK,
+        Err(e) => {
+            eprintln!("{e:#}");
+            exit::FAILURE
+        }
+    }
+}
+
+// Records until SIGINT or SIGTERM, a limit, or wf-recorder failing
+async fn record(config: Config, state: &Path) -> Result<()> {
+    preflight(&config.output_dir, config.min_free_space())?;
+
+    let mut recorder = Recorder::new(config.recording_config());
+    recorder.start()?;
+    let started_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let publish = |recorder: &Recorder| {
+        RecordingState {
+            pid: std::process::id(),
+            output: recorder
+                .output_file()
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+            part: recorder.part(),
+            started_at,
+        }
+        .save(state)
+    };
+    if let Err(e) = publish(&recorder) {
+        let _ = recorder.stop().await;
+        return Err(e);
+    }
+    if let Some(output) = recorder.output_file() {
+        eprintln!("Recording to {}", output.display());
+    }
+
+    let result = supervise(&config, &mut recorder, publish).await;
+    // `stop` forgets the file, so remember where the last part went
+    let output = recorder.output_file().map(PathBuf::from);
+    let stopped = recorder.stop().await;
+    RecordingState::remove(state);
+
+    result?;
+    if let Some(status) = stopped?.filter(|status| !status.success()) {
+        anyhow::bail!("wf-recorder exited with {status}");
+    }
+    // The saved file on stdout, for scripts
+    if let Some(output) = output {
+        println!("{}", output.display());
+    }
+    Ok(())
+}
+
+// Watches the signals, limits and wf-recorder until the recording has to end
+async fn supervise(
+    config: &Config,
+    recorder: &mut Recorder,
+    publish: impl Fn(&Recorder) -> Result<()>,
+) -> Result<()> {
+    let mut interrupt = signal(SignalKind::interrupt()).context("Failed to watch SIGINT")?;
+    let mut terminate = signal(SignalKind::terminate()).context("Failed to watch SIGTERM")?;
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let mut events = recorder.events().boxed();
+    let started = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = interrupt.recv() => return Ok(()),
+            _ = terminate.recv() => return Ok(()),
+            event = events.next() => match event {
+                Some(RecorderEvent::Finished(exit)) => {
+                    anyhow::bail!(
+                        "wf-recorder exited with {}: {}",
+                        exit.status,
+                        exit.stderr_tail.join("\n")
+                    );
+                }
+                Some(RecorderEvent::Warning(message) | RecorderEvent::Error(message)) => {
+                    eprintln!("{message}");
+                }
+                Some(_) => {}
+                // Nothing more to hear from this wf-recorder until the next part
+                None => events = futures::stream::pending().boxed(),
+            },
+            _ = tick.tick() => {
+                if config.max_duration().is_some_and(|limit| started.elapsed() >= limit) {
+                    eprintln!("Reached the duration limit");
+                    return Ok(());
+                }
+                if config
+                    .max_file_size()
+                    .is_some_and(|limit| recorder.recorded_bytes() >= limit)
+                {
+                    eprintln!("Reached the file size limit");
+                    return Ok(());
+                }
+                if let Some(dir) = recorder.output_dir() {
+                    if let DiskSpace::Exhausted(free) = config.disk_watchdog().check(dir) {
+                        eprintln!("Only {} MB left on the disk", free / (1024 * 1024));
+                        return Ok(());
+                    }
+                }
+                if recorder.part_due() {
+                    recorder.next_part()?;
+                    events = recorder.events().boxed();
+                    publish(recorder)?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parse_args(line: &[&str]) -> Result<CliCommand> {
+        parse(&args(line))
+    }
+
+    fn error(line: &[&str]) -> String {
+        format!("{:#}", parse_args(line).unwrap_err())
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_args(&[]).unwrap(), CliCommand::Gui);
+        assert_eq!(parse_args(&["start"]).unwrap(), CliCommand::Start(vec![]));
+        assert_eq!(parse_args(&["toggle"]).unwrap(), CliCommand::Toggle(vec![]));
+        assert_eq!(parse_args(&["stop"]).unwrap(), CliCommand::Stop);
+        assert_eq!(parse_args(&["status"]).unwrap(), CliCommand::Status);
+        assert_eq!(parse_args(&["--doctor"]).unwrap(), CliCommand::Doctor);
+        assert_eq!(parse_args(&["--help"]).unwrap(), CliCommand::Help);
+        assert_eq!(parse_args(&["-h"]).unwrap(), CliCommand::Help);
+    }
+
+    #[test]
+    fn every_override() {
+        let overrides = vec![
+            Override::Format(OutputFormat::Mkv),
+            Override::Codec(VideoCodec::Av1Svt),
+            Override::Quality(QualityPreset::Custom),
+            Override::Encoder(EncoderBackend::Vaapi),
+            Override::Framerate(Framerate::new(60).unwrap()),
+            Override::Audio(AudioSource::Microphone),
+            Override::Region(CaptureRegion::Selection),
+            Override::Region(CaptureRegion::Output(String::from("DP-1"))),
+            Override::OutputDir(PathBuf::from("/tmp/clips")),
+            Override::Filename(String::from("{kind} %H:%M")),
+            Override::MaxDuration(10),
+            Override::MaxSize(700),
+            Override::Split(5),
+        ];
+        assert_eq!(
+            parse_args(&[
+                "start",
+                "--format",
+                "mkv",
+                "--codec=av1-svt",
+                "--quality",
+                "custom",
+                "--encoder",
+                "VAAPI",
+                "--framerate=60",
+                "--audio",
+                "mic",
+                "--region",
+                "select",
+                "--monitor",
+                "DP-1",
+                "--output-dir",
+                "/tmp/clips",
+                "--filename",
+                "{kind} %H:%M",
+                "--max-duration",
+                "10",
+                "--max-size",
+                "700",
+                "--split=5",
+            ])
+            .unwrap(),
+            CliCommand::Start(overrides)
+        );
+        assert_eq!(
+            parse_args(&["toggle", "--quality", "Lossless"]).unwrap(),
+            CliCommand::Toggle(vec![Override::Quality(QualityPreset::Lossless)])
+        );
+    }
+
+    #[test]
+    fn unknown_commands_and_options() {
+        assert_eq!(error(&["record"]), "Unknown command record");
+        assert_eq!(error(&["start", "--verbose"]), "--verbose needs a value");
+        assert_eq!(
+            error(&["start", "--verbose", "yes"]),
+            "Unknown option --verbose"
+        );
+        assert_eq!(error(&["toggle", "-f=mkv"]), "Unknown option -f");
+        for command in ["stop", "status", "--doctor", "--help"] {
+            assert_eq!(
+                error(&[command, "--format", "mkv"]),
+                format!("{command} takes no options")
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            error(&["start", "--quality", "best"]),
+            "--quality has to be one of draft, balanced, high, lossless, custom"
+        );
+        assert_eq!(
+            error(&["start", "--format", "avi"]),
+            "--format has to be one of webm, mp4, mkv"
+        );
+        assert_eq!(error(&["start", "--monitor"]), "--monitor needs a value");
+        for framerate in ["0", "241", "thirty"] {
+            assert_eq!(
+                error(&["start", "--framerate", framerate]),
+                format!(
+                    "--framerate has to be between {} and {}",
+                    Framerate::MIN,
+                    Framerate::MAX
+                )
+            );
+        }
+        for value in ["0", "-5", "1.5", ""] {
+            assert_eq!(
+                error(&["start", &format!("--split={value}")]),
+                "--split has to be a positive whole number"
+            );
+        }
+    }
+
+    #[test]
+    fn exit_codes() {
+        // Keeps the state, lock and socket of a real instance out of reach
+        let runtime_dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-cli-runtime-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&runtime_dir);
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        let paths = Paths {
+            state: runtime_dir.join("recording.json"),
+            lock: runtime_dir.join("instance.lock"),
+            socket: runtime_dir.join("control.sock"),
+        };
+        let run = |command| run(command, &paths);
+
+        assert_eq!(
+            usage_error(&parse_args(&["record"]).unwrap_err()),
+            exit::USAGE
+        );
+        assert_eq!(run(CliCommand::Gui), exit::OK);
+        assert_eq!(run(CliCommand::Help), exit::OK);
+        assert_eq!(run(CliCommand::Stop), exit::NOT_RECORDING);
+        assert_eq!(run(CliCommand::Status), exit::NOT_RECORDING);
+
+        // Refused before the saved settings are read
+        assert_eq!(
+            error(&["start", "--format", "webm", "--codec", "h264"]),
+            "webm files cannot hold libx264"
+        );
+        assert_eq!(
+            error(&["toggle", "--codec=h264", "--format=mkv", "--format=webm"]),
+            "webm files cannot hold libx264"
+        );
+        assert!(parse_args(&["start", "--format=webm", "--codec=vp9"]).is_ok());
+
+        // A state file whose pid does not hold the lock is stale, and this
+        // process must not be sent SIGINT for it
+        let state = RecordingState {
+            pid: std::process::id(),
+            output: runtime_dir.join("recording.mkv"),
+            part: None,
+            started_at: 0,
+        };
+        state.save(&paths.state).unwrap();
+        assert_eq!(run(CliCommand::Stop), exit::NOT_RECORDING);
+        assert!(!paths.state.exists());
+
+        // This process stands in for a running headless recording
+        let lock = InstanceLock::acquire(&paths.lock, Owner::Recording)
+            .unwrap()
+            .unwrap();
+        state.save(&paths.state).unwrap();
+        assert_eq!(run(CliCommand::Start(vec![])), exit::ALREADY_RECORDING);
+        assert_eq!(run(CliCommand::Status), exit::OK);
+        drop(lock);
+
+        std::fs::remove_dir_all(&runtime_dir).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::instance::{self, Holder, Owner};
+
+/// What a headless recording publishes about itself, so `stop` and `status`
+/// run from another process can find it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingState {
+    pub pid: u32,
+    /// File being written, the current part when splitting
+    pub output: PathBuf,
+    pub part: Option<usize>,
+    /// Unix time the recording started at
+    pub started_at: u64,
+}
+
+impl RecordingState {
+    /// `$XDG_RUNTIME_DIR/wf-recorder-gui/recording.json`, or the temp dir
+    /// without one.
+    pub fn path() -> PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("wf-recorder-gui")
+            .join("recording.json")
+    }
+
+    /// The running recording published at `path`, if any. A file left behind
+    /// by a process that no longer holds the instance `lock` is removed.
+    pub fn load(path: &Path, lock: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let state: Self = serde_json::from_str(&content).ok()?;
+        if state.is_running(lock) {
+            Some(state)
+        } else {
+            let _ = fs::remove_file(path);
+            None
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn remove(path: &Path) {
+        let _ = fs::remove_file(path);
+    }
+
+    /// Whether the recording process is still running. The pid alone could
+    /// have been reused after a crash, so it has to be the process holding
+    /// the instance `lock` for a recording; the kernel drops that lock with
+    /// the process.
+    pub fn is_running(&self, lock: &Path) -> bool {
+        instance::holder(lock)
+            == Some(Holder {
+                pid: self.pid,
+                owner: Owner::Recording,
+            })
+    }
+}
@@ -2,15 +2,50 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use crate::recorder::{AudioSource, CaptureRegion, OutputFormat};
+use crate::recorder::{
+    AudioSettings, AudioSource, CaptureRegion, CustomQuality, DiskWatchdog, EncoderBackend,
+    FilenameTemplate, Framerate, OutputFormat, QualityPreset, RecordingConfig, Statvfs, VideoCodec,
+    DEFAULT_FILENAME_TEMPLATE,
+};
 
+// Fields missing from older config files fall back to their defaults
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub output_dir: PathBuf,
+    /// Name for new files, see `FilenameTemplate` for the tokens
+    pub filename_template: String,
     pub format: OutputFormat,
+    pub codec: VideoCodec,
+    pub quality: QualityPreset,
+    pub custom_quality: CustomQuality,
+    pub encoder_backend: EncoderBackend,
+    /// Render node for VAAPI, the first one found is used when unset
+    pub hardware_device: Option<PathBuf>,
     pub audio: AudioSource,
+    /// Sources to record system audio and the microphone from, the sound
+    /// server defaults are used when unset
+    pub system_audio_device: Option<String>,
+    pub microphone_device: Option<String>,
+    pub audio_settings: AudioSettings,
     pub region: CaptureRegion,
+    pub framerate: Framerate,
+    /// How many minutes the replay buffer keeps
+    pub replay_minutes: u32,
+    /// Recordings stop on their own after this many minutes or megabytes
+    pub max_duration_minutes: Option<u32>,
+    pub max_file_size_mb: Option<u64>,
+    /// Length of each part file in minutes, one file is written when unset
+    pub split_minutes: Option<u32>,
+    /// Free space the save location needs before a recording starts
+    pub min_free_space_mb: u64,
+    /// Free space below which the compact bar warns while recording
+    pub low_space_warning_mb: u64,
+    /// Free space below which a recording is stopped while its file can still
+    /// be finalized
+    pub low_space_stop_mb: u64,
 }
 
 impl Default for Config {
@@ -22,9 +57,29 @@ impl Default for Config {
 
         Self {
             output_dir: default_dir,
+            filename_template: String::from(DEFAULT_FILENAME_TEMPLATE),
             format: OutputFormat::Mp4,
+            codec: OutputFormat::Mp4.default_codec(),
+            quality: QualityPreset::Balanced,
+            custom_quality: CustomQuality::default(),
+            encoder_backend: EncoderBackend::Software,
+            hardware_device: None,
             audio: AudioSource::None,
+            system_audio_device: None,
+            microphone_device: None,
+            audio_settings: AudioSettings {
+                codec: OutputFormat::Mp4.default_audio_codec(),
+                ..AudioSettings::default()
+            },
             region: CaptureRegion::FullScreen,
+            framerate: Framerate::default(),
+            replay_minutes: 5,
+            max_duration_minutes: None,
+            max_file_size_mb: None,
+            split_minutes: None,
+            min_free_space_mb: 1024,
+            low_space_warning_mb: 2048,
+            low_space_stop_mb: 256,
         }
     }
 }
@@ -53,23 +108,45 @@ impl Config {
             let content = fs::read_to_string(&path)?;
             let mut config: Config = serde_json::from_str(&content)?;
 
-            // Ensure the output directory exists and create ~/Videos/Screencasts if needed
-            if !config.output_dir.exists() {
-                // If saved dir doesn't exist, try to create it or use default
-                if config
+            // Recreate ~/Videos/Screencasts if needed. Other missing
+            // directories are kept so starting a recording can report them
+            // instead of saving somewhere unexpected
+            if !config.output_dir.exists()
+                && config
                     .output_dir
                     .to_string_lossy()
                     .contains("Videos/Screencasts")
-                {
-                    fs::create_dir_all(&config.output_dir).ok();
-                }
-
-                // If it still doesn't exist after creation attempt, use default
-                if !config.output_dir.exists() {
-                    config.output_dir = Self::default().output_dir;
-                }
+            {
+                fs::create_dir_all(&config.output_dir).ok();
+            }
+
+            // A template left half-typed in the settings is not worth failing over
+            if FilenameTemplate::parse(&config.filename_template).is_err() {
+                config.filename_template = String::from(DEFAULT_FILENAME_TEMPLATE);
+            }
+
+            // Hand-edited files may contain a rate wf-recorder would reject
+            if !config.framerate.is_valid() {
+                config.framerate = Framerate::default();
             }
 
+            // Older files only stored the container, or a mismatched pair
+            if !config.format.supports(config.codec) {
+                config.codec = config.format.default_codec();
+            }
+            config.audio_settings = config.audio_settings.for_format(config.format);
+
+            // An empty window would prune every segment as soon as it ends
+            if config.replay_minutes == 0 {
+                config.replay_minutes = Self::default().replay_minutes;
+            }
+            // A zero limit would stop every recording right away
+            config.max_duration_minutes = config.max_duration_minutes.filter(|&m| m > 0);
+            config.max_file_size_mb = config.max_file_size_mb.filter(|&mb| mb > 0);
+            config.split_minutes = config.split_minutes.filter(|&m| m > 0);
+            // Stopping has to come after the warning, not before it
+            config.low_space_warning_mb = config.low_space_warning_mb.max(config.low_space_stop_mb);
+
             Ok(config)
         } else {
             let config = Self::default();
@@ -85,6 +162,61 @@ impl Config {
         }
     }
 
+    /// Settings for the next recording.
+    pub fn recording_config(&self) -> RecordingConfig {
+        RecordingConfig {
+            format: self.format,
+            codec: self.codec,
+            quality: self.quality,
+            custom_quality: self.custom_quality.clone(),
+            encoder_backend: self.encoder_backend,
+            hardware_device: self.hardware_device.clone(),
+            audio: self.audio,
+            system_audio_device: self.system_audio_device.clone(),
+            microphone_device: self.microphone_device.clone(),
+            audio_settings: self.audio_settings.clone(),
+            region: self.region.clone(),
+            framerate: self.framerate,
+            output_dir: self.output_dir.clone(),
+            filename_template: self.filename_template.clone(),
+            split_after: self
+                .split_minutes
+                .map(|minutes| Duration::from_secs(u64::from(minutes) * 60)),
+            wf_recorder: PathBuf::from("wf-recorder"),
+        }
+    }
+
+    /// How far back the replay buffer reaches.
+    pub fn replay_window(&self) -> Duration {
+        Duration::from_secs(u64::from(self.replay_minutes) * 60)
+    }
+
+    /// Recorded time after which a recording is stopped.
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_minutes
+            .map(|minutes| Duration::from_secs(u64::from(minutes) * 60))
+    }
+
+    /// Free bytes the save location needs before a recording starts.
+    pub fn min_free_space(&self) -> u64 {
+        self.min_free_space_mb.saturating_mul(1024 * 1024)
+    }
+
+    /// Watches the free space left while recording.
+    pub fn disk_watchdog(&self) -> DiskWatchdog {
+        DiskWatchdog::new(
+            Statvfs,
+            self.low_space_warning_mb.saturating_mul(1024 * 1024),
+            self.low_space_stop_mb.saturating_mul(1024 * 1024),
+        )
+    }
+
+    /// Output size in bytes after which a recording is stopped.
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size_mb
+            .map(|megabytes| megabytes.saturating_mul(1024 * 1024))
+    }
+
     pub fn save(&self) -> Result<()> {
         let path = Self::config_path()?;
         let content = serde_json::to_string_pretty(self)?;
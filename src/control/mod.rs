@@ -0,0 +1,501 @@
+//! Control socket for driving the running app, e.g. from a compositor
+//! keybinding.
+//!
+//! The app listens on `$XDG_RUNTIME_DIR/wf-recorder-gui/control.sock`. Clients
+//! write one JSON request per line and get one JSON response line back for
+//! each, so several requests can share a connection.
+//!
+//! Requests name a `command`:
+//!
+//! | Request                                          | Effect                              |
+//! |--------------------------------------------------|-------------------------------------|
+//! | `{"command":"start"}`                            | Start recording, or resume a pause  |
+//! | `{"command":"stop"}`                             | Stop and save the recording         |
+//! | `{"command":"pause"}`                            | Pause the recording                 |
+//! | `{"command":"status"}`                           | Nothing, only report                |
+//! | `{"command":"set-profile","profile":"high"}`     | Quality preset for the next start   |
+//! | `{"command":"focus"}`                            | Bring the window to the front       |
+//!
+//! Profiles are `draft`, `balanced`, `high`, `lossless` and `custom`.
+//!
+//! Responses carry the state after the command was handled:
+//!
+//! ```json
+//! {"ok":true,"status":{"state":"recording","profile":"high","elapsed_secs":12,"file":"/home/me/Videos/Screencasts/recording_20261016_101500.mp4"}}
+//! ```
+//!
+//! `state` is one of `idle`, `countdown`, `recording`, `paused`, `buffering`
+//! or `stopping`; `file` is the file being written, the current part's when
+//! splitting, and is left out when there is none. A command that cannot be
+//! carried out, or a line that is not a request, gets
+//! `{"ok":false,"error":"..."}` along with the status when there is one.
+//!
+//! For example with socat:
+//!
+//! ```sh
+//! echo '{"command":"stop"}' | socat - UNIX-CONNECT:$XDG_RUNTIME_DIR/wf-recorder-gui/control.sock
+//! ```
+
+use anyhow::{Context, Result};
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::recorder::QualityPreset;
+
+// How long `request` waits for the app to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command read from the socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    Start,
+    Stop,
+    Pause,
+    Status,
+    SetProfile {
+        #[serde(with = "profile")]
+        profile: QualityPreset,
+    },
+    Focus,
+}
+
+// Profiles go by their lowercase names, as in file names and on the command line
+mod profile {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::profile_name;
+    use crate::recorder::QualityPreset;
+
+    pub fn serialize<S: Serializer>(
+        preset: &QualityPreset,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(profile_name(*preset))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<QualityPreset, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        QualityPreset::all()
+            .iter()
+            .copied()
+            .find(|preset| profile_name(*preset) == name.to_lowercase())
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown profile {name}")))
+    }
+}
+
+/// Lowercase name of `preset` as the protocol spells it.
+pub fn profile_name(preset: QualityPreset) -> &'static str {
+    match preset {
+        QualityPreset::Draft => "draft",
+        QualityPreset::Balanced => "balanced",
+        QualityPreset::High => "high",
+        QualityPreset::Lossless => "lossless",
+        QualityPreset::Custom => "custom",
+    }
+}
+
+/// What the app is doing, as reported to clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Status {
+    pub state: &'static str,
+    pub profile: &'static str,
+    pub elapsed_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// Answer to one request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok(status: Status) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+
+    pub fn error(error: impl Into<String>, status: Option<Status>) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+            status,
+        }
+    }
+}
+
+/// Where the response to a request goes. Cloning shares it; only the first
+/// `send` reaches the client.
+#[derive(Clone)]
+pub struct Reply(Arc<Mutex<Option<oneshot::Sender<Response>>>>);
+
+impl Reply {
+    pub fn send(&self, response: Response) {
+        let sender = self.0.lock().ok().and_then(|mut sender| sender.take());
+        // The client may have hung up already
+        if let Some(sender) = sender {
+            let _ = sender.send(response);
+        }
+    }
+}
+
+impl fmt::Debug for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reply")
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/wf-recorder-gui/control.sock`, or the temp dir without
+/// a runtime dir.
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("wf-recorder-gui")
+        .join("control.sock")
+}
+
+/// Sends `request` to the app listening at `path` and waits for its
+/// response, for use outside the app.
+pub fn request(path: &Path, request: &Request) -> Result<serde_json::Value> {
+    let mut stream = std::os::unix::net::UnixStream::connect(path)
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut response = String::new();
+    std::io::BufReader::new(stream)
+        .read_line(&mut response)
+        .context("The app did not answer")?;
+    serde_json::from_str(&response).context("The app sent an invalid response")
+}
+
+// Removes the socket file along with the listener
+struct Listener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// The listener, and the requests its connections have read
+struct Server {
+    listener: Listener,
+    requests_tx: mpsc::UnboundedSender<(Request, Reply)>,
+    requests: mpsc::UnboundedReceiver<(Request, Reply)>,
+}
+
+/// Requests arriving on a socket at `path`, each with the `Reply` to answer
+/// it through. Lines that are not requests are answered here.
+///
+/// Ends right away when the socket cannot be created, or another process
+/// is already listening on it. The socket file is removed when the stream is
+/// dropped.
+pub fn listen(path: PathBuf) -> impl Stream<Item = (Request, Reply)> {
+    stream::unfold(None, move |server: Option<Server>| {
+        let path = path.clone();
+        async move {
+            let mut server = match server {
+                Some(server) => server,
+                None => {
+                    let (requests_tx, requests) = mpsc::unbounded_channel();
+                    Server {
+                        listener: bind(&path).await?,
+                        requests_tx,
+                        requests,
+                    }
+                }
+            };
+
+            loop {
+                tokio::select! {
+                    accepted = server.listener.listener.accept() => {
+                        if let Ok((stream, _)) = accepted {
+                            tokio::spawn(serve(stream, server.requests_tx.clone()));
+                        }
+                    }
+                    Some(request) = server.requests.recv() => {
+                        return Some((request, Some(server)));
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn bind(path: &Path) -> Option<Listener> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).ok()?;
+        // Anyone who can reach the socket can start a recording
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)).ok()?;
+    }
+
+    if path.exists() {
+        // A socket that still answers belongs to a running instance
+        if UnixStream::connect(path).await.is_ok() {
+            return None;
+        }
+        // Left behind by a crash
+        std::fs::remove_file(path).ok()?;
+    }
+
+    let listener = UnixListener::bind(path).ok()?;
+    Some(Listener {
+        listener,
+        path: path.to_path_buf(),
+    })
+}
+
+// Answers the requests on one connection in order
+async fn serve(stream: UnixStream, requests: mpsc::UnboundedSender<(Request, Reply)>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (tx, rx) = oneshot::channel();
+                if requests
+                    .send((request, Reply(Arc::new(Mutex::new(Some(tx))))))
+                    .is_err()
+                {
+                    return;
+                }
+                rx.await
+                    .unwrap_or_else(|_| Response::error("The request was dropped", None))
+            }
+            Err(e) => Response::error(format!("Invalid request: {e}"), None),
+        };
+
+        let Ok(mut response) = serde_json::to_string(&response) else {
+            return;
+        };
+        response.push('\n');
+        if writer.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use tokio::io::Lines;
+    use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+
+    fn socket(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-control-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("control.sock")
+    }
+
+    fn status(state: &'static str) -> Status {
+        Status {
+            state,
+            profile: "balanced",
+            elapsed_secs: 0,
+            file: None,
+        }
+    }
+
+    // Stands in for the app: starts and stops, refuses to pause
+    fn serve_app(path: PathBuf) {
+        tokio::spawn(async move {
+            let mut requests = Box::pin(listen(path));
+            let mut state = "idle";
+            while let Some((request, reply)) = requests.next().await {
+                let response = match request {
+                    Request::Start => {
+                        state = "recording";
+                        Response::ok(status(state))
+                    }
+                    Request::Stop => {
+                        state = "idle";
+                        Response::ok(status(state))
+                    }
+                    Request::Pause => Response::error("Not recording", Some(status(state))),
+                    Request::Status | Request::Focus => Response::ok(status(state)),
+                    Request::SetProfile { profile } => Response::ok(Status {
+                        profile: profile_name(profile),
+                        ..status(state)
+                    }),
+                };
+                reply.send(response);
+            }
+        });
+    }
+
+    struct Client {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl Client {
+        async fn connect(path: &Path) -> Self {
+            // The listener binds once the stream is first polled
+            for _ in 0..100 {
+                if let Ok(stream) = UnixStream::connect(path).await {
+                    let (reader, writer) = stream.into_split();
+                    return Self {
+                        lines: BufReader::new(reader).lines(),
+                        writer,
+                    };
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("nothing listening on {}", path.display());
+        }
+
+        async fn send(&mut self, line: &str) -> serde_json::Value {
+            self.writer
+                .write_all(format!("{line}\n").as_bytes())
+                .await
+                .unwrap();
+            let response = self.lines.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&response).unwrap()
+        }
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let parse = |line| serde_json::from_str::<Request>(line).ok();
+        assert_eq!(parse(r#"{"command":"start"}"#), Some(Request::Start));
+        assert_eq!(parse(r#"{"command":"status"}"#), Some(Request::Status));
+        assert_eq!(
+            parse(r#"{"command":"set-profile","profile":"High"}"#),
+            Some(Request::SetProfile {
+                profile: QualityPreset::High
+            })
+        );
+        assert_eq!(
+            parse(r#"{"command":"set-profile","profile":"ultra"}"#),
+            None
+        );
+        assert_eq!(parse(r#"{"command":"record"}"#), None);
+        assert_eq!(parse(r#"{"command":"set-profile"}"#), None);
+        assert_eq!(parse(r#"{"command":"focus"}"#), Some(Request::Focus));
+    }
+
+    #[test]
+    fn requests_are_written_as_parsed() {
+        let request = Request::SetProfile {
+            profile: QualityPreset::Lossless,
+        };
+        let line = serde_json::to_string(&request).unwrap();
+        assert_eq!(line, r#"{"command":"set-profile","profile":"lossless"}"#);
+        assert_eq!(serde_json::from_str::<Request>(&line).unwrap(), request);
+        assert_eq!(
+            serde_json::to_string(&Request::Stop).unwrap(),
+            r#"{"command":"stop"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn client_drives_the_app() {
+        let path = socket("drive");
+        serve_app(path.clone());
+        let mut client = Client::connect(&path).await;
+
+        let response = client.send(r#"{"command":"status"}"#).await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["status"]["state"], "idle");
+
+        let response = client.send(r#"{"command":"start"}"#).await;
+        assert_eq!(response["status"]["state"], "recording");
+
+        let response = client
+            .send(r#"{"command":"set-profile","profile":"lossless"}"#)
+            .await;
+        assert_eq!(response["status"]["profile"], "lossless");
+
+        let response = client.send(r#"{"command":"stop"}"#).await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["status"]["state"], "idle");
+
+        let response = client.send(r#"{"command":"pause"}"#).await;
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["error"], "Not recording");
+        assert_eq!(response["status"]["state"], "idle");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bad_lines_are_answered_without_the_app() {
+        let path = socket("bad");
+        serve_app(path.clone());
+        let mut client = Client::connect(&path).await;
+
+        for line in ["not json", r#"{"command":"explode"}"#, r#"{"cmd":"start"}"#] {
+            let response = client.send(line).await;
+            assert_eq!(response["ok"], false, "{line}");
+            assert!(response["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid request"));
+        }
+        // The connection is still usable afterwards
+        let response = client.send(r#"{"command":"status"}"#).await;
+        assert_eq!(response["ok"], true);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_socket_is_replaced() {
+        let path = socket("stale");
+        // A socket file nobody listens on, as a crash leaves behind
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        serve_app(path.clone());
+        let mut client = Client::connect(&path).await;
+        let response = client.send(r#"{"command":"status"}"#).await;
+        assert_eq!(response["ok"], true);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn running_instance_keeps_its_socket() {
+        let path = socket("taken");
+        serve_app(path.clone());
+        Client::connect(&path).await;
+
+        // A second listener gives up instead of taking over
+        let mut second = Box::pin(listen(path.clone()));
+        assert!(second.next().await.is_none());
+        let mut client = Client::connect(&path).await;
+        let response = client.send(r#"{"command":"status"}"#).await;
+        assert_eq!(response["ok"], true);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
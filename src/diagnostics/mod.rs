@@ -0,0 +1,557 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::recorder::{detect, preflight, AudioSource, HardwareDevice, DEV_DIR};
+
+/// Outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Recording works, but some feature does not
+    Warn,
+    /// Recording will not work until this is fixed
+    Fail,
+}
+
+/// One line of the diagnostics report.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub hint: Option<&'static str>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: &'static str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint),
+        }
+    }
+}
+
+// How long a tool gets to answer before it counts as not answering
+const TOOL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// wf-recorder options the recorder passes, and what needs them
+const WF_RECORDER_OPTIONS: &[(&str, &str)] = &[
+    ("--codec-param", "quality settings"),
+    ("--audio-codec", "audio codec selection"),
+    ("--sample-rate", "sample rate selection"),
+    ("--sample-format", "sample format selection"),
+    ("--audio-codec-param", "audio bitrate"),
+];
+
+// Compositors known to implement wlr-screencopy, as found in XDG_CURRENT_DESKTOP
+const SCREENCOPY_DESKTOPS: &[&str] = &["sway", "hyprland", "river", "wayfire", "labwc", "niri"];
+// Desktops that only offer screen capture through the portal
+const PORTAL_ONLY_DESKTOPS: &[&str] = &["gnome", "kde"];
+
+/// Runs every check against the tools on `PATH`, the session and `config`.
+///
+/// Blocks while the tools answer, so the GUI runs it off the UI thread.
+pub fn run(config: &Config) -> Vec<Check> {
+    // None when wf-recorder is not installed
+    let version = which::which("wf-recorder").ok().map(|_| {
+        output_of("wf-recorder", &["--version"])
+            .and_then(|output| parse_version(&output))
+            .unwrap_or_else(|| String::from("version unknown"))
+    });
+    // None when wf-recorder is missing or does not answer
+    let help = output_of("wf-recorder", &["--help"]);
+    // None without ffmpeg
+    let encoders = output_of("ffmpeg", &["-hide_banner", "-encoders"]);
+    vec![
+        check_wf_recorder(version.as_deref(), help.as_deref()),
+        check_encoders(config, help.as_deref(), encoders.as_deref()),
+        check_optional("slurp", "Selecting a region needs it, install slurp"),
+        check_optional(
+            "ffmpeg",
+            "Pausing, splitting and the replay buffer need it, install ffmpeg",
+        ),
+        check_optional(
+            "pactl",
+            "Picking and mixing audio devices needs it, install pulseaudio-utils or pipewire-pulse",
+        ),
+        check_wayland(),
+        check_screencopy(),
+        check_output_dir(config),
+    ]
+}
+
+/// Whether any check means recording will not work.
+pub fn has_failures(checks: &[Check]) -> bool {
+    checks.iter().any(|check| check.status == CheckStatus::Fail)
+}
+
+/// Plain text version of the report, for `--doctor`.
+pub fn report(checks: &[Check]) -> String {
+    let mut report = String::new();
+    for check in checks {
+        let status = match check.status {
+            CheckStatus::Pass => " OK ",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        let _ = writeln!(report, "[{status}] {:<16} {}", check.name, check.detail);
+        if let Some(hint) = check.hint {
+            let _ = writeln!(report, "       {:<16} {hint}", "");
+        }
+    }
+    report
+}
+
+// Output of `program args`, stdout and stderr together since tools disagree
+// on where help and versions go. A tool that does not exit in time is killed,
+// e.g. an old wf-recorder that starts recording on an unknown option.
+fn output_of(program: &str, args: &[&str]) -> Option<String> {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let pid = child.id();
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    let output = match rx.recv_timeout(TOOL_TIMEOUT) {
+        Ok(output) => output.ok()?,
+        Err(_) => {
+            // SAFETY: kill(2) has no memory safety requirements; the waiting
+            // thread has not reaped the child, so the pid is still ours
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+            return None;
+        }
+    };
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Some(text)
+}
+
+// `version` is None when wf-recorder is not installed
+fn check_wf_recorder(version: Option<&str>, help: Option<&str>) -> Check {
+    const NAME: &str = "wf-recorder";
+    let Some(version) = version else {
+        return Check::fail(
+            NAME,
+            "not found",
+            "Install wf-recorder, nothing can be recorded without it",
+        );
+    };
+
+    let Some(help) = help else {
+        return Check::warn(
+            NAME,
+            format!("{version}, did not answer --help"),
+            "Check that wf-recorder runs from a terminal",
+        );
+    };
+    let missing: Vec<&str> = WF_RECORDER_OPTIONS
+        .iter()
+        .filter(|(option, _)| !has_option(help, option))
+        .map(|(_, feature)| *feature)
+        .collect();
+    if missing.is_empty() {
+        Check::pass(NAME, version.to_string())
+    } else {
+        Check::warn(
+            NAME,
+            format!("{version}, no support for {}", missing.join(", ")),
+            "Update wf-recorder to use every setting",
+        )
+    }
+}
+
+// First thing that looks like a version number, e.g. 0.4.1
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| {
+            word.contains('.')
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c == '.' || c == '-')
+        })
+        .map(String::from)
+}
+
+// Whether wf-recorder, going by its --help, takes the options the configured
+// encoders are passed with, and whether they are in `ffmpeg -encoders`.
+// wf-recorder does not list the encoders its libavcodec was built with, but
+// it links the same libavcodec as the installed ffmpeg.
+fn check_encoders(config: &Config, help: Option<&str>, available: Option<&str>) -> Check {
+    const NAME: &str = "encoders";
+    let Some(help) = help else {
+        return Check::warn(
+            NAME,
+            "cannot ask wf-recorder",
+            "Install wf-recorder to check the configured encoders",
+        );
+    };
+
+    // Without a usable GPU the recording falls back to software encoding
+    let device = detect(
+        config.encoder_backend,
+        config.codec,
+        config.hardware_device.as_deref(),
+        Path::new(DEV_DIR),
+    )
+    .unwrap_or_default();
+    let video = device
+        .as_ref()
+        .and_then(|device| device.encoder(config.codec))
+        .unwrap_or_else(|| config.codec.encoder());
+
+    let mut wanted = vec![(video, "--codec")];
+    if matches!(device, Some(HardwareDevice::Vaapi(_))) {
+        wanted.push((video, "--device"));
+    }
+    if config.audio != AudioSource::None {
+        let audio = config
+            .audio_settings
+            .codec
+            .encoder(config.audio_settings.sample_format);
+        wanted.push((audio, "--audio-codec"));
+    }
+
+    let mut encoders: Vec<&str> = wanted.iter().map(|(encoder, _)| *encoder).collect();
+    encoders.dedup();
+    let mut unusable: Vec<&str> = wanted
+        .iter()
+        .filter(|(_, option)| !has_option(help, option))
+        .map(|(encoder, _)| *encoder)
+        .collect();
+    unusable.dedup();
+
+    if !unusable.is_empty() {
+        return Check::fail(
+            NAME,
+            format!("this wf-recorder cannot use {}", unusable.join(", ")),
+            "Update wf-recorder or pick the default codec",
+        );
+    }
+
+    let Some(available) = available else {
+        return Check::warn(
+            NAME,
+            format!(
+                "{}, cannot list encoders without ffmpeg",
+                encoders.join(", ")
+            ),
+            "Install ffmpeg to check that the configured encoders are available",
+        );
+    };
+    let missing: Vec<&str> = encoders
+        .iter()
+        .filter(|encoder| !has_encoder(available, encoder))
+        .copied()
+        .collect();
+    if missing.is_empty() {
+        Check::pass(NAME, encoders.join(", "))
+    } else {
+        Check::fail(
+            NAME,
+            format!("{} not available", missing.join(", ")),
+            "Pick another codec or install an ffmpeg built with it",
+        )
+    }
+}
+
+// Whether `encoder` is listed in `ffmpeg -encoders`. After a legend ending
+// in a dashed line, each line is the capability flags, the name and a
+// description.
+fn has_encoder(encoders: &str, encoder: &str) -> bool {
+    encoders
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .any(|line| line.split_whitespace().nth(1) == Some(encoder))
+}
+
+// Whether `option` is documented in `help`, not just a longer option
+// starting with it, as --audio-codec is of --audio-codec-param
+fn has_option(help: &str, option: &str) -> bool {
+    help.split(|c: char| c.is_whitespace() || matches!(c, ',' | '=' | '['))
+        .any(|word| word == option)
+}
+
+fn check_optional(program: &'static str, hint: &'static str) -> Check {
+    match which::which(program) {
+        Ok(path) => Check::pass(program, path.display().to_string()),
+        Err(_) => Check::warn(program, "not found", hint),
+    }
+}
+
+fn check_wayland() -> Check {
+    const NAME: &str = "Wayland session";
+    match std::env::var("WAYLAND_DISPLAY") {
+        Ok(display) if !display.is_empty() => Check::pass(NAME, display),
+        _ => Check::fail(
+            NAME,
+            "WAYLAND_DISPLAY is not set",
+            "wf-recorder only records Wayland sessions",
+        ),
+    }
+}
+
+// Asks wayland-info for the protocol where it is installed, otherwise goes by
+// the desktop name
+fn check_screencopy() -> Check {
+    const NAME: &str = "wlr-screencopy";
+    if let Some(globals) = output_of("wayland-info", &[]) {
+        if globals.contains("zwlr_screencopy_manager_v1") {
+            return Check::pass(NAME, "advertised by the compositor");
+        }
+        if globals.contains("wl_compositor") {
+            return Check::fail(
+                NAME,
+                "not advertised by the compositor",
+                "wf-recorder needs a wlroots based compositor such as sway or Hyprland",
+            );
+        }
+    }
+
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+    let known = |names: &[&str]| names.iter().any(|name| desktop.contains(name));
+    if known(SCREENCOPY_DESKTOPS) {
+        Check::pass(NAME, format!("expected on {desktop}"))
+    } else if known(PORTAL_ONLY_DESKTOPS) {
+        Check::fail(
+            NAME,
+            format!("not supported on {desktop}"),
+            "wf-recorder needs a wlroots based compositor such as sway or Hyprland",
+        )
+    } else {
+        Check::warn(
+            NAME,
+            "could not be confirmed",
+            "Install wayland-utils so wayland-info can check the compositor",
+        )
+    }
+}
+
+fn check_output_dir(config: &Config) -> Check {
+    const NAME: &str = "save location";
+    match preflight(&config.output_dir, config.min_free_space()) {
+        Ok(()) => Check::pass(NAME, config.output_dir.display().to_string()),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{e:#}"),
+            "Pick another folder or free up space",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::{AudioCodec, AudioSettings, EncoderBackend, VideoCodec};
+
+    // Trimmed `wf-recorder --help` of 0.4.1
+    const HELP: &str = "Usage: wf-recorder [OPTION]... -f [FILE]...
+Screen recording of wlroots-based compositors
+
+With no FILE, start recording the current screen.
+
+Use Ctrl+C to stop.
+
+  -a, --audio [=DEVICE]     Starts recording the screen with audio.
+                            [=DEVICE] argument is optional.
+                            In case you want to specify the pulseaudio device which will capture
+                            the audio, you can run this command with the name of that device.
+                            You can find your device by running: pactl list sources | grep Name
+                            Specify device like this: -a<device> or --audio=<device>
+
+  -c, --codec               Specifies the codec of the video. These can be found by using:
+                            ffmpeg -encoders
+                            To modify codec parameters, use -p <option_name>=<option_value>
+
+  -r, --framerate           Changes framerate to constant framerate with a given value.
+
+  -d, --device              Selects the device to use when encoding the video
+                            Some drivers report support for rgb0 data for vaapi input but
+                            really only support yuv.
+
+  -C, --audio-codec         Specifies the codec of the audio. These can be found by running:
+                            ffmpeg -encoders
+                            To modify codec parameters, use -P <option_name>=<option_value>
+
+  -X, --sample-format       Changes the audio sample format. These can be found by running:
+                            ffmpeg -sample_fmts
+
+  -R, --sample-rate         Changes the audio sample rate in HZ. The default value is 48000.
+
+  -p, --codec-param         Change the codec parameters.
+                            -p <option_name>=<option_value>
+
+  -P, --audio-codec-param   Change the audio codec parameters.
+                            -P <option_name>=<option_value>
+";
+
+    // Trimmed `ffmpeg -hide_banner -encoders` of a build without libopus
+    const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D h264_vaapi           H.264/AVC (VAAPI) (codec h264)
+ V....D libvpx-vp9           libvpx VP9 (codec vp9)
+ VF.... ffv1                 FFmpeg video codec #1
+ A....D aac                  AAC (Advanced Audio Coding)
+ A..X.. opus                 Opus
+ A....D flac                 FLAC (Free Lossless Audio Codec)
+";
+
+    fn config(audio: AudioSource) -> Config {
+        let config = Config::default();
+        Config {
+            codec: VideoCodec::Vp9,
+            encoder_backend: EncoderBackend::Software,
+            audio,
+            audio_settings: AudioSettings {
+                codec: AudioCodec::Opus,
+                ..config.audio_settings
+            },
+            ..config
+        }
+    }
+
+    #[test]
+    fn encoders_are_checked_against_ffmpeg() {
+        let available = format!("{ENCODERS} A....D libopus            libopus Opus\n");
+        let check = check_encoders(&config(AudioSource::System), Some(HELP), Some(&available));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.detail, "libvpx-vp9, libopus");
+
+        let check = check_encoders(&config(AudioSource::None), Some(HELP), Some(ENCODERS));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.detail, "libvpx-vp9");
+    }
+
+    #[test]
+    fn missing_encoders_fail() {
+        // Only the native opus, not libopus
+        let check = check_encoders(&config(AudioSource::System), Some(HELP), Some(ENCODERS));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.detail, "libopus not available");
+
+        let config = Config {
+            codec: VideoCodec::Av1Svt,
+            ..config(AudioSource::System)
+        };
+        let check = check_encoders(&config, Some(HELP), Some(ENCODERS));
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.detail, "libsvtav1, libopus not available");
+
+        // Legend lines do not count as encoders
+        assert!(!has_encoder(ENCODERS, "="));
+        assert!(has_encoder(ENCODERS, "h264_vaapi"));
+        assert!(!has_encoder(ENCODERS, "h264"));
+    }
+
+    #[test]
+    fn encoders_without_ffmpeg() {
+        let check = check_encoders(&config(AudioSource::None), Some(HELP), None);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(
+            check.detail,
+            "libvpx-vp9, cannot list encoders without ffmpeg"
+        );
+    }
+
+    #[test]
+    fn old_wf_recorder_cannot_pick_the_audio_codec() {
+        let help = HELP.replace("-C, --audio-codec", "");
+        let check = check_encoders(
+            &config(AudioSource::Microphone),
+            Some(&help),
+            Some(ENCODERS),
+        );
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.detail, "this wf-recorder cannot use libopus");
+
+        // Not recording audio, so the codec does not matter
+        let check = check_encoders(&config(AudioSource::None), Some(&help), Some(ENCODERS));
+        assert_eq!(check.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn encoders_without_wf_recorder() {
+        let check = check_encoders(&config(AudioSource::None), None, Some(ENCODERS));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.detail, "cannot ask wf-recorder");
+    }
+
+    #[test]
+    fn wf_recorder_options() {
+        let check = check_wf_recorder(Some("0.4.1"), Some(HELP));
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(check.detail, "0.4.1");
+
+        let help = HELP.replace("--sample-rate", "");
+        let check = check_wf_recorder(Some("0.4.1"), Some(&help));
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.detail, "0.4.1, no support for sample rate selection");
+
+        let check = check_wf_recorder(Some("version unknown"), None);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.detail, "version unknown, did not answer --help");
+
+        let check = check_wf_recorder(None, None);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.detail, "not found");
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(
+            parse_version("wf-recorder 0.4.1\n").as_deref(),
+            Some("0.4.1")
+        );
+        assert_eq!(
+            parse_version("wf-recorder v0.5.0").as_deref(),
+            Some("0.5.0")
+        );
+        assert_eq!(parse_version("unknown"), None);
+    }
+}
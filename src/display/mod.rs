@@ -0,0 +1,489 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::process::Command;
+
+/// A monitor as reported by the compositor.
+///
+/// Position and size are in logical pixels, i.e. after scaling and rotation,
+/// which is the coordinate space windows are laid out in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    /// Connector name wf-recorder expects for `-o`, e.g. `DP-1`
+    pub name: String,
+    pub description: String,
+    /// Whether the compositor considers this output focused
+    pub focused: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: f32,
+}
+
+// Parses the JSON printed by one of the output listing tools
+type OutputParser = fn(&str) -> Result<Vec<Output>>;
+
+/// Lists enabled outputs, asking the running compositor first and falling
+/// back to `wlr-randr`. Empty when none of them could be queried.
+pub fn list_outputs() -> Vec<Output> {
+    let queries: [(&str, &[&str], OutputParser); 3] = [
+        ("hyprctl", &["monitors", "-j"], parse_hyprland_monitors),
+        (
+            "swaymsg",
+            &["-t", "get_outputs", "--raw"],
+            parse_sway_outputs,
+        ),
+        ("wlr-randr", &["--json"], parse_wlr_randr),
+    ];
+
+    for (program, args, parse) in queries {
+        match query(program, args).and_then(|json| parse(&json)) {
+            Ok(outputs) if !outputs.is_empty() => return outputs,
+            _ => continue,
+        }
+    }
+
+    Vec::new()
+}
+
+/// The output a full screen recording should capture: the focused one, or
+/// the first one when the compositor does not track focus.
+pub fn primary_output(outputs: &[Output]) -> Option<&Output> {
+    outputs
+        .iter()
+        .find(|output| output.focused)
+        .or_else(|| outputs.first())
+}
+
+/// The output the app window is on.
+///
+/// Wayland does not tell clients where their window is, so the compositor is
+/// asked for the window belonging to this process and the output holding its
+/// centre is returned. Where that is not possible, e.g. with only wlr-randr,
+/// or before the window is mapped, this is the primary output instead.
+pub fn current_output() -> Option<Output> {
+    let outputs = list_outputs();
+    window_center(std::process::id())
+        .and_then(|(x, y)| output_at(&outputs, x, y))
+        .or_else(|| primary_output(&outputs))
+        .cloned()
+}
+
+/// The output containing the logical point `x`, `y`.
+pub fn output_at(outputs: &[Output], x: i32, y: i32) -> Option<&Output> {
+    outputs.iter().find(|output| {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (left, top) = (i64::from(output.x), i64::from(output.y));
+        x >= left
+            && x < left + i64::from(output.width)
+            && y >= top
+            && y < top + i64::from(output.height)
+    })
+}
+
+// Centre of the window `pid` owns, in the logical coordinates outputs use
+fn window_center(pid: u32) -> Option<(i32, i32)> {
+    let queries: [(&str, &[&str], WindowParser); 2] = [
+        ("hyprctl", &["clients", "-j"], parse_hyprland_clients),
+        ("swaymsg", &["-t", "get_tree", "--raw"], parse_sway_tree),
+    ];
+
+    queries.into_iter().find_map(|(program, args, parse)| {
+        query(program, args).ok().and_then(|json| parse(&json, pid))
+    })
+}
+
+// Finds the centre of a process's window in the JSON a compositor printed
+type WindowParser = fn(&str, u32) -> Option<(i32, i32)>;
+
+fn query(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        anyhow::bail!("{program} exited with {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses `wlr-randr --json`.
+pub fn parse_wlr_randr(json: &str) -> Result<Vec<Output>> {
+    #[derive(Deserialize)]
+    struct WlrOutput {
+        name: String,
+        #[serde(default)]
+        description: String,
+        #[serde(default = "enabled_by_default")]
+        enabled: bool,
+        #[serde(default)]
+        modes: Vec<WlrMode>,
+        #[serde(default)]
+        position: Option<WlrPosition>,
+        #[serde(default)]
+        transform: Option<String>,
+        #[serde(default)]
+        scale: Option<f32>,
+    }
+
+    #[derive(Deserialize)]
+    struct WlrMode {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        current: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct WlrPosition {
+        x: i32,
+        y: i32,
+    }
+
+    let outputs: Vec<WlrOutput> = serde_json::from_str(json).context("Invalid wlr-randr output")?;
+    Ok(outputs
+        .into_iter()
+        .filter(|output| output.enabled)
+        .map(|output| {
+            let scale = output.scale.unwrap_or(1.0);
+            let (width, height) = output
+                .modes
+                .iter()
+                .find(|mode| mode.current)
+                .map(|mode| {
+                    // Transforms are "normal", "90", "flipped-270", ...
+                    let rotated = output
+                        .transform
+                        .as_deref()
+                        .is_some_and(|t| t.ends_with("90") || t.ends_with("270"));
+                    logical_size(mode.width, mode.height, scale, rotated)
+                })
+                .unwrap_or_default();
+            let (x, y) = output
+                .position
+                .map(|position| (position.x, position.y))
+                .unwrap_or_default();
+
+            Output {
+                name: output.name,
+                description: output.description,
+                focused: false,
+                x,
+                y,
+                width,
+                height,
+                scale,
+            }
+        })
+        .collect())
+}
+
+/// Parses `swaymsg -t get_outputs --raw`.
+pub fn parse_sway_outputs(json: &str) -> Result<Vec<Output>> {
+    #[derive(Deserialize)]
+    struct SwayOutput {
+        name: String,
+        #[serde(default)]
+        make: String,
+        #[serde(default)]
+        model: String,
+        #[serde(default = "enabled_by_default")]
+        active: bool,
+        #[serde(default)]
+        focused: bool,
+        // Already in logical pixels
+        #[serde(default)]
+        rect: SwayRect,
+        #[serde(default)]
+        scale: Option<f32>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct SwayRect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    }
+
+    let outputs: Vec<SwayOutput> = serde_json::from_str(json).context("Invalid swaymsg output")?;
+    Ok(outputs
+        .into_iter()
+        .filter(|output| output.active)
+        .map(|output| Output {
+            description: format!("{} {}", output.make, output.model)
+                .trim()
+                .to_string(),
+            name: output.name,
+            focused: output.focused,
+            x: output.rect.x,
+            y: output.rect.y,
+            width: output.rect.width,
+            height: output.rect.height,
+            scale: output.scale.unwrap_or(1.0),
+        })
+        .collect())
+}
+
+/// Parses `hyprctl monitors -j`.
+pub fn parse_hyprland_monitors(json: &str) -> Result<Vec<Output>> {
+    #[derive(Deserialize)]
+    struct HyprlandMonitor {
+        name: String,
+        #[serde(default)]
+        description: String,
+        #[serde(default)]
+        disabled: bool,
+        #[serde(default)]
+        focused: bool,
+        // Mode size in physical pixels
+        #[serde(default)]
+        width: u32,
+        #[serde(default)]
+        height: u32,
+        #[serde(default)]
+        x: i32,
+        #[serde(default)]
+        y: i32,
+        #[serde(default)]
+        scale: Option<f32>,
+        // wl_output transform, odd values are rotated by 90 or 270 degrees
+        #[serde(default)]
+        transform: u32,
+    }
+
+    let monitors: Vec<HyprlandMonitor> =
+        serde_json::from_str(json).context("Invalid hyprctl output")?;
+    Ok(monitors
+        .into_iter()
+        .filter(|monitor| !monitor.disabled)
+        .map(|monitor| {
+            let scale = monitor.scale.unwrap_or(1.0);
+            let (width, height) = logical_size(
+                monitor.width,
+                monitor.height,
+                scale,
+                monitor.transform % 2 == 1,
+            );
+
+            Output {
+                name: monitor.name,
+                description: monitor.description,
+                focused: monitor.focused,
+                x: monitor.x,
+                y: monitor.y,
+                width,
+                height,
+                scale,
+            }
+        })
+        .collect())
+}
+
+/// Centre of the window `pid` owns in `hyprctl clients -j`.
+pub fn parse_hyprland_clients(json: &str, pid: u32) -> Option<(i32, i32)> {
+    #[derive(Deserialize)]
+    struct HyprlandClient {
+        pid: i64,
+        #[serde(default = "enabled_by_default")]
+        mapped: bool,
+        #[serde(default)]
+        hidden: bool,
+        at: [i32; 2],
+        size: [i32; 2],
+    }
+
+    let clients: Vec<HyprlandClient> = serde_json::from_str(json).ok()?;
+    clients
+        .into_iter()
+        .find(|client| client.pid == i64::from(pid) && client.mapped && !client.hidden)
+        .map(|client| {
+            (
+                client.at[0] + client.size[0] / 2,
+                client.at[1] + client.size[1] / 2,
+            )
+        })
+}
+
+/// Centre of the window `pid` owns in `swaymsg -t get_tree --raw`.
+pub fn parse_sway_tree(json: &str, pid: u32) -> Option<(i32, i32)> {
+    #[derive(Deserialize)]
+    struct SwayNode {
+        #[serde(default)]
+        pid: Option<u32>,
+        #[serde(default)]
+        rect: SwayRect,
+        #[serde(default)]
+        nodes: Vec<SwayNode>,
+        #[serde(default)]
+        floating_nodes: Vec<SwayNode>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct SwayRect {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    }
+
+    fn find(node: &SwayNode, pid: u32) -> Option<&SwayRect> {
+        if node.pid == Some(pid) {
+            return Some(&node.rect);
+        }
+        node.nodes
+            .iter()
+            .chain(&node.floating_nodes)
+            .find_map(|child| find(child, pid))
+    }
+
+    let root: SwayNode = serde_json::from_str(json).ok()?;
+    find(&root, pid).map(|rect| (rect.x + rect.width / 2, rect.y + rect.height / 2))
+}
+
+// Converts a mode size in physical pixels to the logical size windows see
+fn logical_size(width: u32, height: u32, scale: f32, rotated: bool) -> (u32, u32) {
+    let scale = if scale > 0.0 { scale } else { 1.0 };
+    let width = (width as f32 / scale).round() as u32;
+    let height = (height as f32 / scale).round() as u32;
+    if rotated {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, focused: bool, rect: (i32, i32, u32, u32), scale: f32) -> Output {
+        Output {
+            name: name.to_string(),
+            description: String::new(),
+            focused,
+            x: rect.0,
+            y: rect.1,
+            width: rect.2,
+            height: rect.3,
+            scale,
+        }
+    }
+
+    // Compares everything but the descriptions, which are checked separately
+    fn without_descriptions(outputs: Vec<Output>) -> Vec<Output> {
+        outputs
+            .into_iter()
+            .map(|output| Output {
+                description: String::new(),
+                ..output
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wlr_randr() {
+        let outputs = parse_wlr_randr(include_str!("fixtures/wlr-randr.json")).unwrap();
+        assert_eq!(
+            outputs[1].description,
+            "Dell Inc. DELL U2419H 5KX2VT2 (DP-2 via HDMI)"
+        );
+        assert_eq!(
+            without_descriptions(outputs),
+            [
+                // 2560x1440 at 1.5
+                output("eDP-1", false, (0, 0, 1707, 960), 1.5),
+                output("DP-2", false, (1707, 0, 1080, 1920), 1.0),
+                output("DP-3", false, (2787, 0, 720, 1280), 2.0),
+                // HDMI-A-1 is disabled; a headless output has no mode to size it by
+                output("HEADLESS-1", false, (4067, 0, 0, 0), 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn sway() {
+        let outputs = parse_sway_outputs(include_str!("fixtures/sway.json")).unwrap();
+        assert_eq!(outputs[1].description, "Dell Inc. DELL U2419H");
+        // The inactive HDMI-A-1 has neither a mode nor a scale
+        assert_eq!(
+            without_descriptions(outputs),
+            [
+                output("eDP-1", false, (0, 0, 1707, 960), 1.5),
+                output("DP-2", true, (1707, 0, 1080, 1920), 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn hyprland() {
+        let outputs = parse_hyprland_monitors(include_str!("fixtures/hyprland.json")).unwrap();
+        assert_eq!(outputs[0].description, "BOE 0x0BCA");
+        assert_eq!(
+            without_descriptions(outputs),
+            [
+                // 1920x1200 at 1.25
+                output("eDP-1", false, (0, 0, 1536, 960), 1.25),
+                // Transform 1 is 90 degrees, 7 is flipped and 270 degrees
+                output("DP-2", true, (1536, 0, 1080, 1920), 1.0),
+                output("DP-3", false, (2616, 0, 720, 1280), 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_json_is_an_error() {
+        assert!(parse_wlr_randr("wlr-randr: failed to connect to display").is_err());
+        assert!(parse_sway_outputs("").is_err());
+        assert!(parse_hyprland_monitors("{\"ok\": true}").is_err());
+    }
+
+    #[test]
+    fn hyprland_window() {
+        let json = include_str!("fixtures/hyprland-clients.json");
+        assert_eq!(parse_hyprland_clients(json, 4242), Some((3116, 530)));
+        assert_eq!(parse_hyprland_clients(json, 1), None);
+        assert_eq!(parse_hyprland_clients("", 4242), None);
+    }
+
+    #[test]
+    fn sway_window() {
+        let json = include_str!("fixtures/sway-tree.json");
+        // Floating windows are found as well as tiled ones
+        assert_eq!(parse_sway_tree(json, 4242), Some((2247, 960)));
+        assert_eq!(parse_sway_tree(json, 1812), Some((853, 480)));
+        assert_eq!(parse_sway_tree(json, 1), None);
+    }
+
+    #[test]
+    fn window_is_matched_to_its_output() {
+        let outputs = parse_sway_outputs(include_str!("fixtures/sway.json")).unwrap();
+        let window = parse_sway_tree(include_str!("fixtures/sway-tree.json"), 4242).unwrap();
+        assert_eq!(
+            output_at(&outputs, window.0, window.1).unwrap().name,
+            "DP-2"
+        );
+
+        // Right and bottom edges belong to the next output
+        assert_eq!(output_at(&outputs, 0, 0).unwrap().name, "eDP-1");
+        assert_eq!(output_at(&outputs, 1706, 959).unwrap().name, "eDP-1");
+        assert_eq!(output_at(&outputs, 1707, 0).unwrap().name, "DP-2");
+        assert_eq!(output_at(&outputs, 100, 960), None);
+        assert_eq!(output_at(&outputs, -1, 0), None);
+    }
+
+    #[test]
+    fn primary_is_the_focused_output() {
+        let outputs = [
+            output("eDP-1", false, (0, 0, 1920, 1080), 1.0),
+            output("DP-2", true, (1920, 0, 1920, 1080), 1.0),
+        ];
+        assert_eq!(primary_output(&outputs).unwrap().name, "DP-2");
+        assert_eq!(primary_output(&outputs[..1]).unwrap().name, "eDP-1");
+        assert_eq!(primary_output(&[]), None);
+    }
+}
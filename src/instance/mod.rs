@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// What kind of process may be recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Owner {
+    /// The app with its window
+    Window,
+    /// A headless `start`
+    Recording,
+}
+
+/// The process holding the lock, as it wrote itself into the lock file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub pid: u32,
+    pub owner: Owner,
+}
+
+/// Keeps a second window or headless recording from starting next to this
+/// process.
+///
+/// The lock is an flock(2) on the lock file, so the kernel drops it when the
+/// process exits, crashes included. A file left behind is taken over by the
+/// next process; only a live holder keeps others out.
+#[derive(Debug)]
+pub struct InstanceLock {
+    // Locked for as long as it is open. std opens files close-on-exec, so
+    // wf-recorder and other children do not keep the lock alive.
+    _file: File,
+}
+
+impl InstanceLock {
+    /// `$XDG_RUNTIME_DIR/wf-recorder-gui/instance.lock`, or the temp dir
+    /// without one.
+    pub fn path() -> PathBuf {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("wf-recorder-gui")
+            .join("instance.lock")
+    }
+
+    /// Takes the lock at `path` for `owner`. `None` when another process
+    /// holds it, see `holder` for which.
+    pub fn acquire(path: &Path, owner: Owner) -> Result<Option<Self>> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        match lock(&file, libc::LOCK_EX) {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to lock {}", path.display())),
+        }
+
+        // Whatever a crashed holder wrote is replaced
+        let holder = Holder {
+            pid: std::process::id(),
+            owner,
+        };
+        file.set_len(0)
+            .and_then(|()| file.write_all(serde_json::to_string(&holder)?.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+/// The live process holding the lock at `path`, if any. `None` as well when
+/// the holder has not written itself into the file yet.
+pub fn holder(path: &Path) -> Option<Holder> {
+    let mut file = File::open(path).ok()?;
+    // A shared lock succeeds only when nobody holds the lock; it is released
+    // again when the file is closed
+    if lock(&file, libc::LOCK_SH).ok()? {
+        return None;
+    }
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// Tries to lock `file` without blocking, false when it is held elsewhere
+fn lock(file: &File, operation: libc::c_int) -> io::Result<bool> {
+    // SAFETY: flock(2) only operates on the descriptor, which `file` keeps open
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-instance-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("instance.lock")
+    }
+
+    #[test]
+    fn second_instance_is_refused() {
+        let path = lock_path("second");
+        let lock = InstanceLock::acquire(&path, Owner::Window).unwrap();
+        assert!(lock.is_some());
+
+        assert!(InstanceLock::acquire(&path, Owner::Recording)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            holder(&path),
+            Some(Holder {
+                pid: std::process::id(),
+                owner: Owner::Window
+            })
+        );
+
+        // Asking who holds it does not take the lock away
+        assert!(InstanceLock::acquire(&path, Owner::Recording)
+            .unwrap()
+            .is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn lock_is_released_on_drop() {
+        let path = lock_path("drop");
+        drop(InstanceLock::acquire(&path, Owner::Window).unwrap());
+
+        assert_eq!(holder(&path), None);
+        assert!(InstanceLock::acquire(&path, Owner::Recording)
+            .unwrap()
+            .is_some());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn stale_lock_file_is_taken_over() {
+        let path = lock_path("stale");
+        // What a crashed instance leaves behind: a file nobody has locked
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"{"pid":999999,"owner":"window","extra":"garbage"}"#,
+        )
+        .unwrap();
+        assert_eq!(holder(&path), None);
+
+        let _lock = InstanceLock::acquire(&path, Owner::Recording)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            holder(&path),
+            Some(Holder {
+                pid: std::process::id(),
+                owner: Owner::Recording
+            })
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
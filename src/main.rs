@@ -1,25 +1,69 @@
 mod audio;
+mod cli;
 mod config;
+mod control;
+mod diagnostics;
+mod display;
+mod instance;
 mod recorder;
 mod theme;
 
 use anyhow::Result;
-use iced::widget::{button, column, container, pick_list, row, text, Space};
+use chrono::Local;
+use iced::futures::StreamExt;
+use iced::widget::{
+    button, column, container, pick_list, progress_bar, row, scrollable, text, text_input, Space,
+};
 use iced::{
-    alignment, executor, window, Application, Command, Element, Font, Length, Point, Settings,
-    Size, Theme as IcedTheme,
+    alignment, executor, subscription, window, Application, Command, Element, Font, Length, Point,
+    Rectangle, Settings, Size, Subscription, Theme as IcedTheme,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::time::{Duration, Instant};
 
+use audio::AudioDevice;
 use config::Config;
-use recorder::{AudioSource, CaptureRegion, OutputFormat, Recorder, RecordingConfig};
+use diagnostics::{Check, CheckStatus};
+use display::Output;
+use instance::{InstanceLock, Owner};
+use recorder::{
+    detect, free_space, is_valid_bitrate, part_suffix, preflight, render_nodes, AudioCodec,
+    AudioSource, CaptureRegion, DiskSpace, EncoderBackend, FilenameContext, FilenameTemplate,
+    Framerate, OutputFormat, QualityPreset, Recorder, RecorderEvent, RecorderExit, ReplayBuffer,
+    SampleFormat, SampleRate, VideoCodec, DEFAULT_FILENAME_TEMPLATE, DEV_DIR,
+    REPLAY_SEGMENT_LENGTH,
+};
 use theme::{design, Theme};
 
+// How long the compact bar confirms a saved replay
+const REPLAY_SAVED_NOTICE: Duration = Duration::from_secs(3);
+// Replay buffer lengths offered in the settings, in minutes
+const REPLAY_MINUTES: [u32; 6] = [1, 2, 5, 10, 15, 30];
+
 fn main() -> Result<()> {
+    // Commands such as `start` or `--doctor` run without a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let paths = cli::Paths::runtime();
+    match cli::parse(&args) {
+        Ok(cli::CliCommand::Gui) => {}
+        Ok(command) => std::process::exit(cli::run(command, &paths)),
+        Err(e) => std::process::exit(cli::usage_error(&e)),
+    }
+
+    // Two windows would both record; the lock is held until the app exits
+    let _lock = match InstanceLock::acquire(&paths.lock, Owner::Window) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => std::process::exit(cli::already_running(&paths)),
+        Err(e) => {
+            eprintln!("Not guarding against a second instance: {e:#}");
+            None
+        }
+    };
+
     // Detect screen size early to set proper initial window size
-    let screen_size = App::detect_screen_size();
-    let scale_factor = design::scale_factor(screen_size.width, screen_size.height);
+    let screen = App::detect_screen();
+    let scale_factor = design::scale_factor(screen.width, screen.height);
 
     // Calculate optimal initial size
     let initial_size = Size::new(
@@ -55,12 +99,47 @@ fn main() -> Result<()> {
 #[derive(Debug, Clone)]
 enum Message {
     FormatSelected(OutputFormat),
-    ToggleRegion(bool),
+    CodecSelected(VideoCodec),
+    QualitySelected(QualityPreset),
+    CustomCrfChanged(String),
+    CustomPresetChanged(String),
+    CustomBitrateChanged(String),
+    EncoderBackendSelected(EncoderBackend),
+    HardwareDeviceSelected(String),
+    FramerateSelected(Framerate),
+    RegionSelected(CaptureRegion),
     ToggleAudio(AudioSource),
+    AudioDeviceSelected(AudioDevice),
+    AudioCodecSelected(AudioCodec),
+    SampleRateSelected(SampleRate),
+    SampleFormatSelected(SampleFormat),
+    AudioBitrateChanged(String),
+    AudioLevel(audio::Level),
     BrowseFolder,
     FolderSelected(PathBuf),
+    FilenameTemplateChanged(String),
     StartRecording,
+    StartReplayBuffer,
+    SaveReplay,
+    ReplaySaved(Result<PathBuf, String>),
+    ReplayMinutesSelected(u32),
+    ShowDiagnostics,
+    DiagnosticsReady(Vec<Check>),
+    CloseDiagnostics,
+    MaxDurationChanged(String),
+    MaxFileSizeChanged(String),
+    SplitMinutesChanged(String),
+    MinFreeSpaceChanged(String),
+    // A request from the control socket, answered once handled
+    Control(control::Request, control::Reply),
+    PauseRecording,
+    ResumeRecording,
     StopRecording,
+    RecordingStopped(Result<Option<ExitStatus>, String>),
+    // What was recorded before wf-recorder failed has been saved
+    FailedRecordingSaved(Result<(), String>),
+    Recorder(RecorderEvent),
+    DismissError,
     Tick,
     #[allow(dead_code)]
     ResizeWindow(Size),
@@ -75,16 +154,52 @@ enum AppState {
     Settings,
     CompactCountdown(u8),
     CompactRecording,
+    CompactPaused,
+    // Recording into the replay buffer
+    CompactBuffering,
+    CompactStopping,
+    RecordingFailed,
+    Diagnostics,
 }
 
 struct App {
     state: AppState,
     config: Config,
     recorder: Option<Recorder>,
+    // Start of the segment being recorded, unset while paused
     recording_start: Option<Instant>,
+    // Time recorded in the segments before it
+    recording_offset: Duration,
     recording_duration: Duration,
+    // Whether the countdown ends in the replay buffer
+    replay_requested: bool,
+    replay_saving: bool,
+    replay_saved_at: Option<Instant>,
+    failure: Option<RecorderExit>,
+    // Saving what was recorded before the failure, unset while it runs
+    failure_saved: Option<Result<(), String>>,
+    recorder_errors: Vec<String>,
+    recorder_output: Option<String>,
+    recorder_encoder: Option<String>,
+    error: Option<String>,
+    // Why the last recording ended without being stopped, if it hit a limit
+    notice: Option<String>,
+    // Results of the last diagnostics run, unset while it runs
+    diagnostics: Option<Vec<Check>>,
+    // Free space on the filesystem being recorded to
+    disk_space: DiskSpace,
+    outputs: Vec<Output>,
+    audio_sources: audio::Sources,
+    audio_level: audio::Level,
+    render_nodes: Vec<PathBuf>,
+    hardware_status: Option<String>,
+    // Name the next recording gets, or why it cannot be named
+    filename_preview: Result<String, String>,
+    // Free bytes where recordings are saved, unset when unknown
+    output_dir_free: Option<u64>,
     theme: Theme,
-    screen_size: Size,
+    // Monitor the window is on, in logical pixels
+    screen: Rectangle,
     scale_factor: f32,
 }
 
@@ -97,21 +212,20 @@ impl Application for App {
     fn new(_flags: ()) -> (Self, Command<Message>) {
         let config = Config::load().unwrap_or_default();
 
-        // Try to get actual screen size, fallback to safe default
-        let screen_size = Self::detect_screen_size();
-        let scale_factor = design::scale_factor(screen_size.width, screen_size.height);
+        // A previous run may have died while mixing audio
+        audio::remove_leftovers(&audio::Pactl);
 
-        let app = App {
-            state: AppState::Settings,
-            config,
-            recorder: None,
-            recording_start: None,
-            recording_duration: Duration::default(),
-            theme: Theme::default(),
-            screen_size,
-            scale_factor,
+        let mut app = App {
+            outputs: display::list_outputs(),
+            audio_sources: audio::Sources::query(),
+            render_nodes: render_nodes(&Path::new(DEV_DIR).join("dri")),
+            // Try to get actual screen size, fallback to safe default
+            ..App::idle(config, Self::detect_screen())
         };
 
+        app.refresh_hardware_status();
+        app.refresh_save_location();
+
         // Ensure window is properly sized on startup
         let optimal_size = app.get_settings_size();
         let initial_command = window::resize(window::Id::MAIN, optimal_size);
@@ -120,7 +234,12 @@ impl Application for App {
     }
 
     fn title(&self) -> String {
-        String::from("WF Recorder")
+        match (&self.state, &self.recorder_output) {
+            (AppState::CompactRecording, Some(output)) => format!("WF Recorder • {output}"),
+            (AppState::CompactPaused, _) => String::from("WF Recorder • Paused"),
+            (AppState::CompactBuffering, _) => String::from("WF Recorder • Replay Buffer"),
+            _ => String::from("WF Recorder"),
+        }
     }
 
     fn theme(&self) -> IcedTheme {
@@ -130,21 +249,166 @@ impl Application for App {
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::FormatSelected(format) => {
+                self.config.audio_settings = self
+                    .config
+                    .audio_settings
+                    .switch_format(self.config.format, format);
                 self.config.format = format;
+                // Keep the codec legal for the new container
+                if !format.supports(self.config.codec) {
+                    self.config.codec = format.default_codec();
+                }
+                self.refresh_hardware_status();
+                self.refresh_save_location();
                 let _ = self.config.save();
                 Command::none()
             }
-            Message::ToggleRegion(is_fullscreen) => {
-                self.config.region = if is_fullscreen {
-                    CaptureRegion::FullScreen
+            Message::CodecSelected(codec) => {
+                self.config.codec = codec;
+                self.refresh_hardware_status();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::QualitySelected(quality) => {
+                self.config.quality = quality;
+                self.refresh_save_location();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::CustomCrfChanged(crf) => {
+                // Ignore keystrokes that would not leave a number behind
+                if crf.is_empty() {
+                    self.config.custom_quality.crf = None;
+                } else if let Ok(crf) = crf.parse() {
+                    self.config.custom_quality.crf = Some(crf);
                 } else {
-                    CaptureRegion::Selection
-                };
+                    return Command::none();
+                }
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::CustomPresetChanged(preset) => {
+                let preset = preset.trim().to_string();
+                self.config.custom_quality.preset = (!preset.is_empty()).then_some(preset);
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::CustomBitrateChanged(bitrate) => {
+                let bitrate = bitrate.trim().to_string();
+                self.config.custom_quality.bitrate = (!bitrate.is_empty()).then_some(bitrate);
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::EncoderBackendSelected(backend) => {
+                self.config.encoder_backend = backend;
+                self.refresh_hardware_status();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::HardwareDeviceSelected(device) => {
+                self.config.hardware_device = Some(PathBuf::from(device));
+                self.refresh_hardware_status();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::FramerateSelected(framerate) => {
+                self.config.framerate = framerate;
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::RegionSelected(region) => {
+                self.config.region = region;
+                self.refresh_save_location();
                 let _ = self.config.save();
                 Command::none()
             }
             Message::ToggleAudio(source) => {
                 self.config.audio = source;
+                // Pick up devices plugged in since startup
+                self.audio_sources = audio::Sources::query();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::AudioDeviceSelected(device) => {
+                if device.monitor {
+                    self.config.system_audio_device = Some(device.name);
+                } else {
+                    self.config.microphone_device = Some(device.name);
+                }
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::AudioCodecSelected(codec) => {
+                self.config.audio_settings.codec = codec;
+                // Drop a rate or sample format the new encoder cannot take
+                self.config.audio_settings =
+                    self.config.audio_settings.for_format(self.config.format);
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::SampleRateSelected(sample_rate) => {
+                self.config.audio_settings.sample_rate = sample_rate;
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::SampleFormatSelected(sample_format) => {
+                self.config.audio_settings.sample_format = sample_format;
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::AudioBitrateChanged(bitrate) => {
+                let bitrate = bitrate.trim().to_string();
+                self.config.audio_settings.bitrate = (!bitrate.is_empty()).then_some(bitrate);
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::AudioLevel(level) => {
+                self.audio_level = level;
+                Command::none()
+            }
+            Message::MaxDurationChanged(minutes) => {
+                // Ignore keystrokes that would not leave a limit behind
+                if minutes.is_empty() {
+                    self.config.max_duration_minutes = None;
+                } else if let Ok(minutes @ 1..) = minutes.parse() {
+                    self.config.max_duration_minutes = Some(minutes);
+                } else {
+                    return Command::none();
+                }
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::MaxFileSizeChanged(megabytes) => {
+                if megabytes.is_empty() {
+                    self.config.max_file_size_mb = None;
+                } else if let Ok(megabytes @ 1..) = megabytes.parse() {
+                    self.config.max_file_size_mb = Some(megabytes);
+                } else {
+                    return Command::none();
+                }
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::SplitMinutesChanged(minutes) => {
+                if minutes.is_empty() {
+                    self.config.split_minutes = None;
+                } else if let Ok(minutes @ 1..) = minutes.parse() {
+                    self.config.split_minutes = Some(minutes);
+                } else {
+                    return Command::none();
+                }
+                self.refresh_save_location();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::MinFreeSpaceChanged(megabytes) => {
+                if megabytes.is_empty() {
+                    self.config.min_free_space_mb = 0;
+                } else if let Ok(megabytes) = megabytes.parse() {
+                    self.config.min_free_space_mb = megabytes;
+                } else {
+                    return Command::none();
+                }
                 let _ = self.config.save();
                 Command::none()
             }
@@ -169,62 +433,221 @@ impl Application for App {
             }
             Message::FolderSelected(path) => {
                 self.config.output_dir = path;
+                self.refresh_save_location();
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::FilenameTemplateChanged(template) => {
+                self.config.filename_template = template;
+                self.refresh_save_location();
                 let _ = self.config.save();
                 Command::none()
             }
             Message::StartRecording => {
-                let recording_config = RecordingConfig {
-                    format: self.config.format,
-                    audio: self.config.audio,
-                    region: self.config.region,
-                    output_dir: self.config.output_dir.clone(),
+                self.replay_requested = false;
+                self.start_countdown()
+            }
+            Message::StartReplayBuffer => {
+                self.replay_requested = true;
+                self.start_countdown()
+            }
+            Message::SaveReplay => {
+                let Some(recorder) = self.recorder.as_mut() else {
+                    return Command::none();
                 };
+                if self.replay_saving {
+                    return Command::none();
+                }
 
-                let _recorder = Recorder::new(recording_config.clone());
-
-                // Always use compact mode for recording - non-intrusive
-                self.state = AppState::CompactCountdown(3);
-
-                // Handle region selection vs fullscreen
-                let delay = match recording_config.region {
-                    CaptureRegion::Selection => 100,
-                    CaptureRegion::FullScreen => 1000,
+                let output = match recorder.replay_filename() {
+                    Ok(output) => output,
+                    Err(e) => {
+                        self.recorder_errors.push(format!("{e:#}"));
+                        return Command::none();
+                    }
+                };
+                match recorder.snapshot_replay() {
+                    Ok(snapshot) => {
+                        self.replay_saving = true;
+                        Command::perform(
+                            async move { snapshot.save(output).await.map_err(|e| format!("{e:#}")) },
+                            Message::ReplaySaved,
+                        )
+                    }
+                    Err(e) => {
+                        self.recorder_errors.push(format!("{e:#}"));
+                        Command::none()
+                    }
+                }
+            }
+            Message::ReplaySaved(result) => {
+                self.replay_saving = false;
+                match result {
+                    Ok(_) => self.replay_saved_at = Some(Instant::now()),
+                    Err(e) if self.state == AppState::CompactBuffering => {
+                        self.recorder_errors.push(e)
+                    }
+                    // The buffer was stopped while saving, report it there
+                    Err(e) => self.error = Some(e),
+                }
+                Command::none()
+            }
+            Message::ShowDiagnostics => {
+                self.state = AppState::Diagnostics;
+                self.diagnostics = None;
+                let config = self.config.clone();
+                Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || diagnostics::run(&config))
+                            .await
+                            .unwrap_or_default()
+                    },
+                    Message::DiagnosticsReady,
+                )
+            }
+            Message::DiagnosticsReady(checks) => {
+                self.diagnostics = Some(checks);
+                Command::none()
+            }
+            Message::CloseDiagnostics => {
+                self.state = AppState::Settings;
+                Command::none()
+            }
+            Message::ReplayMinutesSelected(minutes) => {
+                self.config.replay_minutes = minutes;
+                let _ = self.config.save();
+                Command::none()
+            }
+            Message::Control(request, reply) => {
+                let (command, refused) = self.handle_control(request);
+                let status = self.control_status();
+                reply.send(match refused {
+                    Some(error) => control::Response::error(error, Some(status)),
+                    None => control::Response::ok(status),
+                });
+                command
+            }
+            Message::PauseRecording => {
+                let Some(recorder) = self.recorder.as_mut() else {
+                    return Command::none();
                 };
+                if let Err(e) = recorder.pause() {
+                    self.recorder_errors.push(format!("{e:#}"));
+                    return Command::none();
+                }
 
-                // Always switch to compact mode for recording
-                let compact_size = self.get_compact_size();
-                let position = self.get_compact_position();
+                if let Some(start) = self.recording_start.take() {
+                    self.recording_offset += start.elapsed();
+                }
+                self.recording_duration = self.recording_offset;
+                self.state = AppState::CompactPaused;
+                Command::none()
+            }
+            Message::ResumeRecording => {
+                let Some(recorder) = self.recorder.as_mut() else {
+                    return Command::none();
+                };
+                if let Err(e) = recorder.resume() {
+                    self.recorder_errors.push(format!("{e:#}"));
+                    return Command::none();
+                }
 
-                Command::batch([
-                    window::resize(window::Id::MAIN, compact_size),
-                    window::move_to(window::Id::MAIN, position),
-                    Command::perform(
-                        async move {
-                            tokio::time::sleep(Duration::from_millis(delay)).await;
-                        },
-                        |_| Message::Tick,
-                    ),
-                ])
+                self.recording_start = Some(Instant::now());
+                self.state = AppState::CompactRecording;
+                Command::none()
             }
             Message::StopRecording => {
-                if let Some(recorder) = &mut self.recorder {
-                    let _ = recorder.stop();
-                }
-                self.recorder = None;
-                self.state = AppState::Settings;
                 self.recording_start = None;
+                self.recording_offset = Duration::default();
                 self.recording_duration = Duration::default();
 
-                // Return to normal settings window size and center
-                let settings_size = self.get_settings_size();
-                let center_position = self.get_center_position(settings_size);
+                let Some(mut recorder) = self.recorder.take() else {
+                    // Cancelled during the countdown, nothing to finalize
+                    return self.return_to_settings();
+                };
+
+                // Keep the compact bar up until wf-recorder has finished the file
+                self.state = AppState::CompactStopping;
+                Command::perform(
+                    async move { recorder.stop().await.map_err(|e| format!("{e:#}")) },
+                    Message::RecordingStopped,
+                )
+            }
+            Message::RecordingStopped(result) => {
+                match result {
+                    Ok(Some(status)) if !status.success() => {
+                        self.error = Some(format!("wf-recorder exited with {status}"));
+                    }
+                    Err(e) => self.error = Some(format!("Failed to stop recording: {e}")),
+                    _ => {}
+                }
+                self.return_to_settings()
+            }
+            Message::FailedRecordingSaved(result) => {
+                self.failure_saved = Some(result);
+                Command::none()
+            }
+            Message::Recorder(event) => match event {
+                RecorderEvent::Warning(message) | RecorderEvent::Error(message) => {
+                    self.recorder_errors.push(message);
+                    Command::none()
+                }
+                RecorderEvent::Finished(exit) => {
+                    // Exits after StopRecording are handled by RecordingStopped
+                    if !matches!(
+                        self.state,
+                        AppState::CompactRecording | AppState::CompactBuffering
+                    ) {
+                        return Command::none();
+                    }
 
-                Command::batch([
-                    window::resize(window::Id::MAIN, settings_size),
-                    window::move_to(window::Id::MAIN, center_position),
-                ])
+                    // Still join whatever was recorded up to the failure
+                    let save =
+                        match self.recorder.take() {
+                            Some(mut recorder) => {
+                                self.failure_saved = None;
+                                Command::perform(
+                                    async move {
+                                        recorder.stop().await.map(|_| ()).map_err(|e| {
+                                            format!("Failed to save the recording: {e:#}")
+                                        })
+                                    },
+                                    Message::FailedRecordingSaved,
+                                )
+                            }
+                            None => {
+                                self.failure_saved = Some(Ok(()));
+                                Command::none()
+                            }
+                        };
+                    self.recording_start = None;
+                    self.recording_offset = Duration::default();
+                    self.recording_duration = Duration::default();
+                    self.failure = Some(exit);
+                    self.state = AppState::RecordingFailed;
+                    Command::batch([save, self.restore_window()])
+                }
+                RecorderEvent::OutputSelected(output) => {
+                    self.recorder_output = Some(output);
+                    Command::none()
+                }
+                RecorderEvent::EncoderInfo(info) => {
+                    self.recorder_encoder = Some(info);
+                    Command::none()
+                }
+                RecorderEvent::Started => Command::none(),
+            },
+            Message::DismissError => {
+                self.failure = None;
+                self.return_to_settings()
             }
             Message::Tick => {
+                // Stop while the file can still be finalized
+                if let Some(reason) = self.watch_disk_space() {
+                    self.notice = Some(reason);
+                    return self.update(Message::StopRecording);
+                }
+
                 match self.state {
                     AppState::CompactCountdown(count) => {
                         if count > 1 {
@@ -237,28 +660,35 @@ impl Application for App {
                             )
                         } else {
                             // Start recording
-                            let recording_config = RecordingConfig {
-                                format: self.config.format,
-                                audio: self.config.audio,
-                                region: self.config.region,
-                                output_dir: self.config.output_dir.clone(),
-                            };
+                            let recording_config = self.config.recording_config();
 
                             let mut recorder = Recorder::new(recording_config);
-                            if let Err(e) = recorder.start() {
-                                eprintln!("Failed to start recording: {e}");
-                                self.state = AppState::Settings;
-                                let settings_size = self.get_settings_size();
-                                let center_position = self.get_center_position(settings_size);
-                                Command::batch([
-                                    window::resize(window::Id::MAIN, settings_size),
-                                    window::move_to(window::Id::MAIN, center_position),
-                                ])
+                            let started = if self.replay_requested {
+                                let buffer = ReplayBuffer::new(
+                                    ReplayBuffer::default_dir(),
+                                    self.config.replay_window(),
+                                );
+                                recorder.start_replay(buffer)
+                            } else {
+                                recorder.start()
+                            };
+                            if let Err(e) = started {
+                                self.error = Some(format!("{e:#}"));
+                                self.return_to_settings()
                             } else {
                                 self.recorder = Some(recorder);
+                                self.recorder_errors.clear();
+                                self.recorder_output = None;
+                                self.recorder_encoder = None;
+                                self.replay_saved_at = None;
                                 // Always use compact recording mode - non-intrusive
-                                self.state = AppState::CompactRecording;
+                                self.state = if self.replay_requested {
+                                    AppState::CompactBuffering
+                                } else {
+                                    AppState::CompactRecording
+                                };
                                 self.recording_start = Some(Instant::now());
+                                self.recording_offset = Duration::default();
 
                                 // Start timer updates
                                 Command::perform(
@@ -270,9 +700,47 @@ impl Application for App {
                             }
                         }
                     }
-                    AppState::CompactRecording => {
+                    // Paused time is not counted, but the tick keeps running
+                    // so resuming does not start a second one
+                    AppState::CompactRecording | AppState::CompactPaused => {
+                        if let Some(start) = self.recording_start {
+                            self.recording_duration = self.recording_offset + start.elapsed();
+                        }
+                        if let Some(reason) = self.limit_reached() {
+                            self.notice = Some(reason);
+                            return self.update(Message::StopRecording);
+                        }
+                        if let Some(recorder) = self.recorder.as_mut() {
+                            if recorder.part_due() {
+                                if let Err(e) = recorder.next_part() {
+                                    self.recorder_errors.push(format!("{e:#}"));
+                                }
+                            }
+                        }
+                        Command::perform(
+                            async {
+                                tokio::time::sleep(Duration::from_secs(1)).await;
+                            },
+                            |_| Message::Tick,
+                        )
+                    }
+                    // Starts a new segment every REPLAY_SEGMENT_LENGTH so old
+                    // ones can be dropped as they leave the window
+                    AppState::CompactBuffering => {
+                        if let Some(recorder) = self.recorder.as_mut() {
+                            if recorder
+                                .segment_age()
+                                .is_some_and(|age| age >= REPLAY_SEGMENT_LENGTH)
+                            {
+                                if let Err(e) = recorder.rotate() {
+                                    self.recorder_errors.push(format!("{e:#}"));
+                                }
+                            }
+                            recorder.prune_replay();
+                        }
                         if let Some(start) = self.recording_start {
-                            self.recording_duration = start.elapsed();
+                            self.recording_duration =
+                                start.elapsed().min(self.config.replay_window());
                         }
                         Command::perform(
                             async {
@@ -281,7 +749,10 @@ impl Application for App {
                             |_| Message::Tick,
                         )
                     }
-                    AppState::Settings => Command::none(),
+                    AppState::Settings
+                    | AppState::CompactStopping
+                    | AppState::RecordingFailed
+                    | AppState::Diagnostics => Command::none(),
                 }
             }
             Message::ResizeWindow(size) => window::resize(window::Id::MAIN, size),
@@ -294,14 +765,21 @@ impl Application for App {
         let content = match self.state {
             AppState::Settings => self.view_settings(),
             AppState::CompactCountdown(count) => self.view_compact_countdown(count),
-            AppState::CompactRecording => self.view_compact_recording(),
+            AppState::CompactRecording | AppState::CompactPaused | AppState::CompactBuffering => {
+                self.view_compact_recording()
+            }
+            AppState::CompactStopping => self.view_compact_stopping(),
+            AppState::RecordingFailed => self.view_recording_failed(),
+            AppState::Diagnostics => self.view_diagnostics(),
         };
 
         // Dynamic window padding based on scale factor and mode
         let padding = match self.state {
-            AppState::CompactCountdown(_) | AppState::CompactRecording => {
-                design::COMPACT_BUTTON_PADDING
-            }
+            AppState::CompactCountdown(_)
+            | AppState::CompactRecording
+            | AppState::CompactPaused
+            | AppState::CompactBuffering
+            | AppState::CompactStopping => design::COMPACT_BUTTON_PADDING,
             _ => design::window_padding(self.scale_factor),
         };
 
@@ -315,53 +793,106 @@ impl Application for App {
             )))
             .into()
     }
+
+    fn subscription(&self) -> Subscription<Message> {
+        // Follow wf-recorder's output until it has been asked to stop
+        let events = match (&self.state, &self.recorder) {
+            (AppState::CompactRecording | AppState::CompactBuffering, Some(recorder)) => {
+                subscription::run_with_id(recorder.pid(), recorder.events().map(Message::Recorder))
+            }
+            _ => Subscription::none(),
+        };
+
+        // Meter the audio wherever its level is on screen; a new device
+        // restarts the capture
+        let meter = match (&self.state, self.meter_device()) {
+            (
+                AppState::Settings
+                | AppState::CompactRecording
+                | AppState::CompactPaused
+                | AppState::CompactBuffering,
+                Some(device),
+            ) => subscription::run_with_id(
+                ("audio-meter", device.clone()),
+                audio::monitor(Some(device)).map(Message::AudioLevel),
+            ),
+            _ => Subscription::none(),
+        };
+
+        // Keybindings drive the app through the control socket
+        let control = subscription::run_with_id(
+            "control-socket",
+            control::listen(control::socket_path())
+                .map(|(request, reply)| Message::Control(request, reply)),
+        );
+
+        Subscription::batch([events, meter, control])
+    }
 }
 
 impl App {
-    // Detect screen size using multiple methods
-    pub fn detect_screen_size() -> Size {
-        // Method 1: Check environment variables
+    // The settings view for `config` on `screen`, before the outputs, audio
+    // sources and GPUs are looked up
+    fn idle(config: Config, screen: Rectangle) -> Self {
+        App {
+            state: AppState::Settings,
+            config,
+            recorder: None,
+            recording_start: None,
+            recording_offset: Duration::default(),
+            recording_duration: Duration::default(),
+            replay_requested: false,
+            replay_saving: false,
+            replay_saved_at: None,
+            failure: None,
+            failure_saved: None,
+            recorder_errors: Vec::new(),
+            recorder_output: None,
+            recorder_encoder: None,
+            error: None,
+            notice: None,
+            diagnostics: None,
+            disk_space: DiskSpace::Plenty,
+            outputs: Vec::new(),
+            audio_sources: audio::Sources::default(),
+            audio_level: audio::Level::default(),
+            render_nodes: Vec::new(),
+            hardware_status: None,
+            filename_preview: Ok(String::new()),
+            output_dir_free: None,
+            theme: Theme::default(),
+            screen,
+            scale_factor: design::scale_factor(screen.width, screen.height),
+        }
+    }
+
+    // Logical geometry of the monitor the window is on
+    pub fn detect_screen() -> Rectangle {
+        // Explicit override, e.g. for compositors without an output listing tool
         if let (Ok(width), Ok(height)) = (
             std::env::var("SCREEN_WIDTH")
                 .and_then(|w| w.parse::<f32>().map_err(|_| std::env::VarError::NotPresent)),
             std::env::var("SCREEN_HEIGHT")
                 .and_then(|h| h.parse::<f32>().map_err(|_| std::env::VarError::NotPresent)),
         ) {
-            return Size::new(width, height);
+            return Rectangle::new(Point::ORIGIN, Size::new(width, height));
         }
 
-        // Method 2: Try xrandr on Linux (common case)
-        if let Ok(output) = std::process::Command::new("xrandr")
-            .arg("--current")
-            .output()
-        {
-            if let Ok(output_str) = String::from_utf8(output.stdout) {
-                // Parse xrandr output for primary display
-                for line in output_str.lines() {
-                    if line.contains("primary") || line.contains("*") {
-                        if let Some(resolution) = line.split_whitespace().find(|s| {
-                            s.contains("x") && s.chars().next().unwrap_or(' ').is_ascii_digit()
-                        }) {
-                            let parts: Vec<&str> = resolution.split('x').collect();
-                            if parts.len() == 2 {
-                                if let (Ok(w), Ok(h)) =
-                                    (parts[0].parse::<f32>(), parts[1].parse::<f32>())
-                                {
-                                    return Size::new(w, h);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        // Ask the compositor, so scaling and multiple monitors are accounted for
+        if let Some(output) = display::current_output().filter(|o| o.width > 0 && o.height > 0) {
+            return Rectangle::new(
+                Point::new(output.x as f32, output.y as f32),
+                Size::new(output.width as f32, output.height as f32),
+            );
         }
 
-        // Method 3: Common screen size detection based on typical resolutions
-        // This could be expanded with more platform-specific code
-
         // Default fallback - use reference resolution
-        Size::new(design::REFERENCE_WIDTH, design::REFERENCE_HEIGHT)
+        Rectangle::new(
+            Point::ORIGIN,
+            Size::new(design::REFERENCE_WIDTH, design::REFERENCE_HEIGHT),
+        )
     }
+
     fn view_settings(&self) -> Element<'_, Message> {
         let colors = self.theme.colors;
 
@@ -373,19 +904,32 @@ impl App {
 
         // Title with subtitle - responsive sizing
         let title_section = container(
-            column![
-                text("WF Recorder")
-                    .size(title_size)
-                    .font(Font {
-                        weight: iced::font::Weight::Bold,
-                        ..Default::default()
-                    })
-                    .style(iced::theme::Text::Color(colors.text)),
-                text(format!("{} • 30FPS", self.config.format))
+            row![
+                column![
+                    text("WF Recorder")
+                        .size(title_size)
+                        .font(Font {
+                            weight: iced::font::Weight::Bold,
+                            ..Default::default()
+                        })
+                        .style(iced::theme::Text::Color(colors.text)),
+                    text(format!(
+                        "{} • {}",
+                        self.config.format, self.config.framerate
+                    ))
                     .size(subtitle_size)
                     .style(iced::theme::Text::Color(colors.text_secondary)),
+                ]
+                .spacing(design::tiny_space(self.scale_factor) as u16),
+                Space::with_width(Length::Fill),
+                button(text("Diagnostics").size(design::button_text_size(self.scale_factor)))
+                    .on_press(Message::ShowDiagnostics)
+                    .padding([8, 16])
+                    .style(iced::theme::Button::Custom(Box::new(
+                        theme::SecondaryButton(colors)
+                    ))),
             ]
-            .spacing(design::tiny_space(self.scale_factor) as u16),
+            .align_items(alignment::Alignment::Center),
         )
         .width(Length::Fill)
         .padding([0, 0, section_spacing, 0])
@@ -399,86 +943,493 @@ impl App {
                 "🖥",
                 "Screen",
                 matches!(self.config.region, CaptureRegion::FullScreen),
-                Message::ToggleRegion(true),
+                Message::RegionSelected(CaptureRegion::FullScreen),
             ),
             Space::with_width(Length::Fixed(container_padding as f32)),
             self.create_option_button(
                 "◰",
                 "Region",
                 matches!(self.config.region, CaptureRegion::Selection),
-                Message::ToggleRegion(false),
+                Message::RegionSelected(CaptureRegion::Selection),
             ),
         ];
 
-        let capture_section = self.create_section("CAPTURE MODE", capture_buttons);
+        // One card per monitor when there is more than one to choose from
+        let mut capture_options = column![capture_buttons].spacing(container_padding);
+        if self.outputs.len() > 1 {
+            for outputs in self.outputs.chunks(3) {
+                let mut output_buttons = row![].spacing(container_padding);
+                for output in outputs {
+                    output_buttons = output_buttons.push(self.create_option_button(
+                        "🖥",
+                        &output.name,
+                        matches!(&self.config.region, CaptureRegion::Output(name) if *name == output.name),
+                        Message::RegionSelected(CaptureRegion::Output(output.name.clone())),
+                    ));
+                }
+                capture_options = capture_options.push(output_buttons);
+            }
+        }
 
-        // Audio source buttons
-        let audio_buttons = row![
-            self.create_option_button(
-                "🔊",
-                "System",
-                matches!(self.config.audio, AudioSource::System),
-                Message::ToggleAudio(AudioSource::System),
-            ),
-            Space::with_width(Length::Fixed(container_padding as f32)),
-            self.create_option_button(
-                "🎤",
-                "Mic",
-                matches!(self.config.audio, AudioSource::Microphone),
-                Message::ToggleAudio(AudioSource::Microphone),
-            ),
-            Space::with_width(Length::Fixed(container_padding as f32)),
-            self.create_option_button(
-                "🔇",
-                "None",
-                matches!(self.config.audio, AudioSource::None),
-                Message::ToggleAudio(AudioSource::None),
-            ),
-        ];
+        let capture_section = self.create_section("CAPTURE MODE", capture_options);
+
+        // Audio source buttons, two per row to fit the window
+        let audio_buttons = column![
+            row![
+                self.create_option_button(
+                    "🔊",
+                    "System",
+                    matches!(self.config.audio, AudioSource::System),
+                    Message::ToggleAudio(AudioSource::System),
+                ),
+                Space::with_width(Length::Fixed(container_padding as f32)),
+                self.create_option_button(
+                    "🎤",
+                    "Mic",
+                    matches!(self.config.audio, AudioSource::Microphone),
+                    Message::ToggleAudio(AudioSource::Microphone),
+                ),
+            ],
+            row![
+                self.create_option_button(
+                    "🎙",
+                    "Both",
+                    matches!(self.config.audio, AudioSource::Both),
+                    Message::ToggleAudio(AudioSource::Both),
+                ),
+                Space::with_width(Length::Fixed(container_padding as f32)),
+                self.create_option_button(
+                    "🔇",
+                    "None",
+                    matches!(self.config.audio, AudioSource::None),
+                    Message::ToggleAudio(AudioSource::None),
+                ),
+            ],
+        ]
+        .spacing(container_padding);
+
+        // Device pickers for the chosen kind of audio
+        let pickers = match self.config.audio {
+            AudioSource::None => Vec::new(),
+            AudioSource::System => vec![AudioSource::System],
+            AudioSource::Microphone => vec![AudioSource::Microphone],
+            AudioSource::Both => vec![AudioSource::System, AudioSource::Microphone],
+        };
+        let mut device_row = row![].spacing(container_padding);
+        for kind in pickers {
+            device_row = device_row.push(self.audio_device_picker(kind));
+        }
 
-        let audio_section = self.create_section("AUDIO SOURCE", audio_buttons);
+        let mut audio_content = column![audio_buttons].spacing(container_padding);
+        if self.config.audio != AudioSource::None {
+            audio_content = audio_content
+                .push(self.level_meter(Length::Fill, design::small_space(self.scale_factor)))
+                .push(device_row);
+        }
+
+        let audio_section = self.create_section("AUDIO SOURCE", audio_content);
 
-        // Format picker - styled like onagre's search input
+        // Format and frame rate pickers - styled like onagre's search input
         let format_section = self.create_section(
             "OUTPUT FORMAT",
+            row![
+                container(
+                    pick_list(
+                        OutputFormat::all()
+                            .iter()
+                            .map(|(format, _)| *format)
+                            .collect::<Vec<_>>(),
+                        Some(self.config.format),
+                        Message::FormatSelected,
+                    )
+                    .padding([container_padding, container_padding])
+                    .width(Length::Fill)
+                    .text_size(design::input_text_size(self.scale_factor)),
+                )
+                .width(Length::FillPortion(2))
+                .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                    colors,
+                )))),
+                Space::with_width(Length::Fixed(container_padding as f32)),
+                container(
+                    pick_list(
+                        Framerate::common().to_vec(),
+                        Some(self.config.framerate),
+                        Message::FramerateSelected,
+                    )
+                    .padding([container_padding, container_padding])
+                    .width(Length::Fill)
+                    .text_size(design::input_text_size(self.scale_factor)),
+                )
+                .width(Length::FillPortion(1))
+                .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                    colors,
+                )))),
+            ],
+        );
+
+        // Codec and quality pickers - only offers codecs the container can hold
+        let codec_row = row![
             container(
                 pick_list(
-                    vec![OutputFormat::WebM, OutputFormat::Mp4, OutputFormat::Mkv],
-                    Some(self.config.format),
-                    Message::FormatSelected,
+                    self.config.format.codecs().to_vec(),
+                    Some(self.config.codec),
+                    Message::CodecSelected,
                 )
                 .padding([container_padding, container_padding])
                 .width(Length::Fill)
                 .text_size(design::input_text_size(self.scale_factor)),
             )
-            .width(Length::Fill)
+            .width(Length::FillPortion(2))
             .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
                 colors,
             )))),
-        );
+            Space::with_width(Length::Fixed(container_padding as f32)),
+            container(
+                pick_list(
+                    QualityPreset::all().to_vec(),
+                    Some(self.config.quality),
+                    Message::QualitySelected,
+                )
+                .padding([container_padding, container_padding])
+                .width(Length::Fill)
+                .text_size(design::input_text_size(self.scale_factor)),
+            )
+            .width(Length::FillPortion(1))
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            )))),
+        ];
 
-        // Save location
-        let folder_text = self.config.output_dir.to_string_lossy().to_string();
-        let folder_display = if folder_text.len() > 35 {
-            format!("...{}", &folder_text[folder_text.len() - 32..])
-        } else {
-            folder_text
-        };
+        let codec_content: Element<'_, Message> = if self.config.quality == QualityPreset::Custom {
+            let custom = &self.config.custom_quality;
+            let crf = custom.crf.map(|crf| crf.to_string()).unwrap_or_default();
+            let preset = custom.preset.clone().unwrap_or_default();
+            let bitrate = custom.bitrate.clone().unwrap_or_default();
+
+            // Flag a bitrate the encoder would not understand
+            let bitrate_label = if bitrate.is_empty() || is_valid_bitrate(&bitrate) {
+                text("e.g. 2500k or 4M")
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.text_secondary))
+            } else {
+                text("Invalid bitrate, it will be ignored")
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.danger))
+            };
+
+            column![
+                codec_row,
+                Space::with_height(Length::Fixed(design::small_space(self.scale_factor))),
+                row![
+                    text_input("CRF", &crf)
+                        .on_input(Message::CustomCrfChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(container_padding as f32)),
+                    text_input("Preset", &preset)
+                        .on_input(Message::CustomPresetChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(container_padding as f32)),
+                    text_input("Bitrate", &bitrate)
+                        .on_input(Message::CustomBitrateChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                ],
+                Space::with_height(Length::Fixed(design::tiny_space(self.scale_factor))),
+                bitrate_label,
+            ]
+            .into()
+        } else {
+            codec_row.into()
+        };
+
+        let codec_section = self.create_section("VIDEO CODEC & QUALITY", codec_content);
+
+        // Audio encoding, only relevant when audio is recorded
+        let audio_settings = &self.config.audio_settings;
+        let audio_codec = audio_settings.codec;
+        let audio_row = row![
+            container(
+                pick_list(
+                    self.config.format.audio_codecs().to_vec(),
+                    Some(audio_codec),
+                    Message::AudioCodecSelected,
+                )
+                .padding([container_padding, container_padding])
+                .width(Length::Fill)
+                .text_size(design::input_text_size(self.scale_factor)),
+            )
+            .width(Length::FillPortion(1))
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            )))),
+            Space::with_width(Length::Fixed(container_padding as f32)),
+            container(
+                pick_list(
+                    audio_codec.sample_rates().to_vec(),
+                    Some(audio_settings.sample_rate),
+                    Message::SampleRateSelected,
+                )
+                .padding([container_padding, container_padding])
+                .width(Length::Fill)
+                .text_size(design::input_text_size(self.scale_factor)),
+            )
+            .width(Length::FillPortion(1))
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            )))),
+            Space::with_width(Length::Fixed(container_padding as f32)),
+            container(
+                pick_list(
+                    audio_codec.sample_formats().to_vec(),
+                    Some(audio_settings.sample_format),
+                    Message::SampleFormatSelected,
+                )
+                .padding([container_padding, container_padding])
+                .width(Length::Fill)
+                .text_size(design::input_text_size(self.scale_factor)),
+            )
+            .width(Length::FillPortion(1))
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            )))),
+        ];
+
+        // Lossless codecs ignore the bitrate, so only offer it for lossy ones
+        let audio_content: Element<'_, Message> = if audio_codec.is_lossless() {
+            audio_row.into()
+        } else {
+            let bitrate = audio_settings.bitrate.clone().unwrap_or_default();
+            let bitrate_label = if bitrate.is_empty() || is_valid_bitrate(&bitrate) {
+                text("Bitrate, e.g. 160k. The encoder default is used when empty")
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.text_secondary))
+            } else {
+                text("Invalid bitrate, it will be ignored")
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.danger))
+            };
+
+            column![
+                audio_row,
+                Space::with_height(Length::Fixed(design::small_space(self.scale_factor))),
+                text_input("Audio bitrate", &bitrate)
+                    .on_input(Message::AudioBitrateChanged)
+                    .padding(container_padding)
+                    .size(design::button_text_size(self.scale_factor)),
+                Space::with_height(Length::Fixed(design::tiny_space(self.scale_factor))),
+                bitrate_label,
+            ]
+            .into()
+        };
+
+        let audio_encoding_section = self.create_section("AUDIO ENCODING", audio_content);
+
+        // Encoder backend and, for VAAPI, the render node to use
+        let backend_picker = container(
+            pick_list(
+                EncoderBackend::all().to_vec(),
+                Some(self.config.encoder_backend),
+                Message::EncoderBackendSelected,
+            )
+            .padding([container_padding, container_padding])
+            .width(Length::Fill)
+            .text_size(design::input_text_size(self.scale_factor)),
+        )
+        .width(Length::FillPortion(1))
+        .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+            colors,
+        ))));
+
+        let encoder_row = if self.config.encoder_backend == EncoderBackend::Vaapi
+            && !self.render_nodes.is_empty()
+        {
+            let nodes: Vec<String> = self
+                .render_nodes
+                .iter()
+                .map(|node| node.display().to_string())
+                .collect();
+            let selected = self
+                .config
+                .hardware_device
+                .as_ref()
+                .map(|device| device.display().to_string())
+                .filter(|device| nodes.contains(device))
+                .or_else(|| nodes.first().cloned());
+
+            row![
+                backend_picker,
+                Space::with_width(Length::Fixed(container_padding as f32)),
+                container(
+                    pick_list(nodes, selected, Message::HardwareDeviceSelected)
+                        .padding([container_padding, container_padding])
+                        .width(Length::Fill)
+                        .text_size(design::input_text_size(self.scale_factor)),
+                )
+                .width(Length::FillPortion(2))
+                .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                    colors,
+                )))),
+            ]
+        } else {
+            row![backend_picker]
+        };
+
+        let encoder_content: Element<'_, Message> = match &self.hardware_status {
+            Some(status) => column![
+                encoder_row,
+                Space::with_height(Length::Fixed(design::tiny_space(self.scale_factor))),
+                text(status)
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.danger)),
+            ]
+            .into(),
+            None => encoder_row.into(),
+        };
+
+        let encoder_section = self.create_section("ENCODER", encoder_content);
+
+        // Save location
+        let folder_text = self.config.output_dir.to_string_lossy().to_string();
+        let folder_display = if folder_text.len() > 35 {
+            format!("...{}", &folder_text[folder_text.len() - 32..])
+        } else {
+            folder_text
+        };
+
+        // What the next recording would be called, or why it cannot be named
+        let (preview, preview_color) = match &self.filename_preview {
+            Ok(name) => (name.clone(), colors.text_secondary),
+            Err(e) => (e.clone(), colors.danger),
+        };
+
+        let free_space_label = match self.output_dir_free {
+            Some(free) => format!("MB free, {} MB available", free / (1024 * 1024)),
+            None => String::from("MB free"),
+        };
 
         let location_section = self.create_section(
             "SAVE LOCATION",
+            column![
+                container(
+                    row![
+                        text(folder_display)
+                            .size(design::button_text_size(self.scale_factor))
+                            .style(iced::theme::Text::Color(colors.text_secondary)),
+                        Space::with_width(Length::Fill),
+                        button(text("Browse").size(design::button_text_size(self.scale_factor)))
+                            .on_press(Message::BrowseFolder)
+                            .padding([8, 16])
+                            .style(iced::theme::Button::Custom(Box::new(
+                                theme::SecondaryButton(colors)
+                            ))),
+                    ]
+                    .align_items(alignment::Alignment::Center),
+                )
+                .padding(container_padding)
+                .width(Length::Fill)
+                .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                    colors,
+                )))),
+                Space::with_height(Length::Fixed(design::small_space(self.scale_factor))),
+                text_input(DEFAULT_FILENAME_TEMPLATE, &self.config.filename_template)
+                    .on_input(Message::FilenameTemplateChanged)
+                    .padding(container_padding)
+                    .size(design::button_text_size(self.scale_factor)),
+                Space::with_height(Length::Fixed(design::tiny_space(self.scale_factor))),
+                text(preview)
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(preview_color)),
+                text(format!(
+                    "{} or strftime codes like %Y",
+                    FilenameTemplate::TOKENS.join(" ")
+                ))
+                .size(design::label_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+                Space::with_height(Length::Fixed(design::small_space(self.scale_factor))),
+                row![
+                    text("Require at least")
+                        .size(design::button_text_size(self.scale_factor))
+                        .style(iced::theme::Text::Color(colors.text_secondary)),
+                    Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                    text_input("0", &self.config.min_free_space_mb.to_string())
+                        .on_input(Message::MinFreeSpaceChanged)
+                        .padding(container_padding)
+                        .width(Length::Fixed(design::FREE_SPACE_INPUT_WIDTH))
+                        .size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                    text(free_space_label)
+                        .size(design::button_text_size(self.scale_factor))
+                        .style(iced::theme::Text::Color(colors.text_secondary)),
+                ]
+                .align_items(alignment::Alignment::Center),
+            ],
+        );
+
+        // Unattended recordings stop on their own once they reach a limit
+        let max_duration = self
+            .config
+            .max_duration_minutes
+            .map(|minutes| minutes.to_string())
+            .unwrap_or_default();
+        let max_file_size = self
+            .config
+            .max_file_size_mb
+            .map(|megabytes| megabytes.to_string())
+            .unwrap_or_default();
+        let split_minutes = self
+            .config
+            .split_minutes
+            .map(|minutes| minutes.to_string())
+            .unwrap_or_default();
+        let limits_section = self.create_section(
+            "LIMITS & SPLITTING",
+            column![
+                row![
+                    text_input("Max minutes", &max_duration)
+                        .on_input(Message::MaxDurationChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(container_padding as f32)),
+                    text_input("Max size in MB", &max_file_size)
+                        .on_input(Message::MaxFileSizeChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(container_padding as f32)),
+                    text_input("Split every N minutes", &split_minutes)
+                        .on_input(Message::SplitMinutesChanged)
+                        .padding(container_padding)
+                        .size(design::button_text_size(self.scale_factor)),
+                ],
+                Space::with_height(Length::Fixed(design::tiny_space(self.scale_factor))),
+                text("Leave empty to record without a limit, into a single file")
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.text_secondary)),
+            ],
+        );
+
+        // How much the replay buffer keeps
+        let replay_section = self.create_section(
+            "REPLAY BUFFER",
             container(
                 row![
-                    text(folder_display)
+                    text("Keep the last")
                         .size(design::button_text_size(self.scale_factor))
                         .style(iced::theme::Text::Color(colors.text_secondary)),
                     Space::with_width(Length::Fill),
-                    button(text("Browse").size(design::button_text_size(self.scale_factor)))
-                        .on_press(Message::BrowseFolder)
-                        .padding([8, 16])
-                        .style(iced::theme::Button::Custom(Box::new(
-                            theme::SecondaryButton(colors)
-                        ))),
+                    pick_list(
+                        REPLAY_MINUTES.to_vec(),
+                        Some(self.config.replay_minutes),
+                        Message::ReplayMinutesSelected,
+                    )
+                    .padding([8, 16])
+                    .text_size(design::button_text_size(self.scale_factor)),
+                    Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                    text("minutes")
+                        .size(design::button_text_size(self.scale_factor))
+                        .style(iced::theme::Text::Color(colors.text_secondary)),
                 ]
                 .align_items(alignment::Alignment::Center),
             )
@@ -505,16 +1456,62 @@ impl App {
             colors,
         ))));
 
-        // Layout with onagre-style spacing
+        // Keeps recording in the background until a replay is saved
+        let replay_button = button(
+            text("Replay Buffer")
+                .size(design::input_text_size(self.scale_factor))
+                .horizontal_alignment(alignment::Horizontal::Center),
+        )
+        .on_press(Message::StartReplayBuffer)
+        .padding([
+            design::button_padding_v(self.scale_factor),
+            design::button_padding_h(self.scale_factor),
+        ])
+        .width(Length::Fill)
+        .style(iced::theme::Button::Custom(Box::new(
+            theme::SecondaryButton(colors),
+        )));
+
+        // Error from the last recording attempt, or why it stopped on its own
+        let error_banner: Element<'_, Message> = match (&self.error, &self.notice) {
+            (Some(error), _) => text(error)
+                .size(design::button_text_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.danger))
+                .into(),
+            (None, Some(notice)) => text(notice)
+                .size(design::button_text_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary))
+                .into(),
+            (None, None) => Space::with_height(Length::Shrink).into(),
+        };
+
+        // Layout with onagre-style spacing, options scroll when the window is short
         container(
             column![
                 title_section,
-                capture_section,
-                audio_section,
-                format_section,
-                location_section,
-                Space::with_height(Length::Fill), // Push button to bottom
-                record_button,
+                scrollable(
+                    column![
+                        capture_section,
+                        audio_section,
+                        format_section,
+                        codec_section
+                    ]
+                    .push_maybe(
+                        (self.config.audio != AudioSource::None).then_some(audio_encoding_section)
+                    )
+                    .push(encoder_section)
+                    .push(location_section)
+                    .push(limits_section)
+                    .push(replay_section)
+                    .spacing(section_spacing),
+                )
+                .height(Length::Fill), // Push button to bottom
+                error_banner,
+                row![
+                    container(record_button).width(Length::FillPortion(2)),
+                    container(replay_button).width(Length::FillPortion(1)),
+                ]
+                .spacing(design::small_space(self.scale_factor) as u16),
             ]
             .spacing(section_spacing),
         )
@@ -614,11 +1611,34 @@ impl App {
         let seconds = self.recording_duration.as_secs() % 60;
         let time_text = format!("{minutes:02}:{seconds:02}");
 
+        // Swap the recording dot for a warning sign once wf-recorder complains
+        let (indicator, indicator_color) = match (self.state, self.recorder_errors.is_empty()) {
+            (_, false) => ("⚠", colors.danger),
+            (AppState::CompactPaused, true) => ("⏸", colors.text_secondary),
+            (AppState::CompactBuffering, true) => ("⟲", colors.primary),
+            (_, true) => ("●", colors.danger),
+        };
+        // The replay buffer saves instead of pausing, with a short
+        // confirmation once the file is written
+        let (action_icon, action_message) = match self.state {
+            AppState::CompactPaused => ("▶", Some(Message::ResumeRecording)),
+            AppState::CompactBuffering if self.replay_saving => ("…", None),
+            AppState::CompactBuffering
+                if self
+                    .replay_saved_at
+                    .is_some_and(|saved| saved.elapsed() < REPLAY_SAVED_NOTICE) =>
+            {
+                ("✓", Some(Message::SaveReplay))
+            }
+            AppState::CompactBuffering => ("💾", Some(Message::SaveReplay)),
+            _ => ("⏸", Some(Message::PauseRecording)),
+        };
+
         container(
             row![
-                text("●")
+                text(indicator)
                     .size(design::COMPACT_ICON_SIZE)
-                    .style(iced::theme::Text::Color(colors.danger)),
+                    .style(iced::theme::Text::Color(indicator_color)),
                 Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
                 text(time_text)
                     .size(design::timer_text_size(self.scale_factor))
@@ -629,13 +1649,59 @@ impl App {
                     })
                     .style(iced::theme::Text::Color(colors.text)),
                 Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+            ]
+            // Free space left once it runs low
+            .push_maybe(match self.disk_space {
+                DiskSpace::Low(free) | DiskSpace::Exhausted(free) => Some(
+                    row![
+                        text(format!("{:.1}G free", free as f64 / 1_073_741_824.0))
+                            .size(design::label_size(self.scale_factor))
+                            .style(iced::theme::Text::Color(colors.danger)),
+                        Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                    ]
+                    .align_items(alignment::Alignment::Center),
+                ),
+                DiskSpace::Plenty => None,
+            })
+            // Split recordings show which part file is being written
+            .push_maybe(self.recorder.as_ref().and_then(Recorder::part).map(|part| {
+                row![
+                    text(format!("#{part}"))
+                        .size(design::label_size(self.scale_factor))
+                        .style(iced::theme::Text::Color(colors.text_secondary)),
+                    Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                ]
+                .align_items(alignment::Alignment::Center)
+            }))
+            .push_maybe((self.config.audio != AudioSource::None).then(|| {
+                row![
+                    self.level_meter(
+                        Length::Fixed(design::COMPACT_METER_WIDTH),
+                        design::COMPACT_METER_HEIGHT,
+                    ),
+                    Space::with_width(Length::Fixed(design::small_space(self.scale_factor))),
+                ]
+                .align_items(alignment::Alignment::Center)
+            }))
+            .push(
+                button(text(action_icon).size(design::COMPACT_ICON_SIZE))
+                    .on_press_maybe(action_message)
+                    .padding(design::COMPACT_BUTTON_PADDING)
+                    .style(iced::theme::Button::Custom(Box::new(theme::CompactButton(
+                        colors,
+                    )))),
+            )
+            .push(Space::with_width(Length::Fixed(design::tiny_space(
+                self.scale_factor,
+            ))))
+            .push(
                 button(text("⏹").size(design::COMPACT_ICON_SIZE))
                     .on_press(Message::StopRecording)
                     .padding(design::COMPACT_BUTTON_PADDING)
                     .style(iced::theme::Button::Custom(Box::new(theme::CompactButton(
-                        colors
+                        colors,
                     )))),
-            ]
+            )
             .align_items(alignment::Alignment::Center),
         )
         .width(Length::Fill)
@@ -649,6 +1715,553 @@ impl App {
         .into()
     }
 
+    // Compact stopping view - shown while wf-recorder finalizes the file
+    fn view_compact_stopping(&self) -> Element<'_, Message> {
+        let colors = self.theme.colors;
+
+        container(
+            text("Saving…")
+                .size(design::timer_text_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x()
+        .center_y()
+        .padding(design::COMPACT_BUTTON_PADDING)
+        .style(iced::theme::Container::Custom(Box::new(
+            theme::CompactStyle(colors),
+        )))
+        .into()
+    }
+
+    // Error view shown when wf-recorder stops on its own while recording
+    fn view_recording_failed(&self) -> Element<'_, Message> {
+        let colors = self.theme.colors;
+        let section_spacing = design::section_spacing(self.scale_factor);
+        let container_padding = design::container_padding(self.scale_factor);
+
+        let (status, stderr_tail) = match &self.failure {
+            Some(exit) => (
+                format!("wf-recorder exited with {}", exit.status),
+                exit.stderr_tail.join("\n"),
+            ),
+            None => (String::from("wf-recorder exited"), String::new()),
+        };
+        let details = [&self.recorder_output, &self.recorder_encoder]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" • ");
+
+        // Prefer the errors wf-recorder reported over its raw output
+        let (output_label, output) = if self.recorder_errors.is_empty() {
+            ("WF-RECORDER OUTPUT", stderr_tail)
+        } else {
+            ("WF-RECORDER ERRORS", self.recorder_errors.join("\n"))
+        };
+
+        // Whether the recording up to the failure made it into a file
+        let (saved, saved_color) = match &self.failure_saved {
+            None => (
+                String::from("Saving what was recorded..."),
+                colors.text_secondary,
+            ),
+            Some(Ok(())) => (String::new(), colors.text_secondary),
+            Some(Err(e)) => (e.clone(), colors.danger),
+        };
+
+        let title_section = column![
+            text("Recording stopped")
+                .size(design::title_size(self.scale_factor))
+                .font(Font {
+                    weight: iced::font::Weight::Bold,
+                    ..Default::default()
+                })
+                .style(iced::theme::Text::Color(colors.danger)),
+            text(status)
+                .size(design::subtitle_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+            text(details)
+                .size(design::label_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+            text(saved)
+                .size(design::label_size(self.scale_factor))
+                .style(iced::theme::Text::Color(saved_color)),
+        ]
+        .spacing(design::tiny_space(self.scale_factor) as u16);
+
+        let output_section = self.create_section(
+            output_label,
+            container(
+                text(if output.is_empty() {
+                    String::from("No output")
+                } else {
+                    output
+                })
+                .size(design::button_text_size(self.scale_factor))
+                .font(Font {
+                    family: iced::font::Family::Monospace,
+                    ..Default::default()
+                })
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+            )
+            .padding(container_padding)
+            .width(Length::Fill)
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            )))),
+        );
+
+        let back_button = button(
+            text("Back to Settings")
+                .size(design::input_text_size(self.scale_factor))
+                .horizontal_alignment(alignment::Horizontal::Center),
+        )
+        .on_press(Message::DismissError)
+        .padding([
+            design::button_padding_v(self.scale_factor),
+            design::button_padding_h(self.scale_factor),
+        ])
+        .width(Length::Fill)
+        .style(iced::theme::Button::Custom(Box::new(theme::PrimaryButton(
+            colors,
+        ))));
+
+        container(
+            column![
+                title_section,
+                output_section,
+                Space::with_height(Length::Fill),
+                back_button,
+            ]
+            .spacing(section_spacing),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(iced::theme::Container::Custom(Box::new(
+            theme::ContainerStyle(colors),
+        )))
+        .into()
+    }
+
+    // Diagnostics view - what is installed and whether recording can work
+    fn view_diagnostics(&self) -> Element<'_, Message> {
+        let colors = self.theme.colors;
+        let section_spacing = design::section_spacing(self.scale_factor);
+        let container_padding = design::container_padding(self.scale_factor);
+
+        let summary = match &self.diagnostics {
+            None => String::from("Checking…"),
+            Some(checks) if diagnostics::has_failures(checks) => {
+                String::from("Recording will not work until the failures are fixed")
+            }
+            Some(checks) if checks.iter().any(|check| check.status == CheckStatus::Warn) => {
+                String::from("Recording works, some features are unavailable")
+            }
+            Some(_) => String::from("Everything is in place"),
+        };
+
+        let title_section = column![
+            text("Diagnostics")
+                .size(design::title_size(self.scale_factor))
+                .font(Font {
+                    weight: iced::font::Weight::Bold,
+                    ..Default::default()
+                })
+                .style(iced::theme::Text::Color(colors.text)),
+            text(summary)
+                .size(design::subtitle_size(self.scale_factor))
+                .style(iced::theme::Text::Color(colors.text_secondary)),
+        ]
+        .spacing(design::tiny_space(self.scale_factor) as u16);
+
+        let checks = self.diagnostics.iter().flatten().map(|check| {
+            let (icon, icon_color) = match check.status {
+                CheckStatus::Pass => ("✓", colors.primary),
+                CheckStatus::Warn => ("!", colors.text_secondary),
+                CheckStatus::Fail => ("✗", colors.danger),
+            };
+            let details = column![
+                text(check.name)
+                    .size(design::button_text_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.text)),
+                text(&check.detail)
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(colors.text_secondary)),
+            ]
+            .push_maybe(check.hint.map(|hint| {
+                text(hint)
+                    .size(design::label_size(self.scale_factor))
+                    .style(iced::theme::Text::Color(icon_color))
+            }));
+
+            container(
+                row![
+                    text(icon)
+                        .size(design::input_text_size(self.scale_factor))
+                        .style(iced::theme::Text::Color(icon_color)),
+                    Space::with_width(Length::Fixed(container_padding as f32)),
+                    details,
+                ]
+                .align_items(alignment::Alignment::Center),
+            )
+            .padding(container_padding)
+            .width(Length::Fill)
+            .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+                colors,
+            ))))
+            .into()
+        });
+
+        let rerun_button = button(
+            text("Run Again")
+                .size(design::input_text_size(self.scale_factor))
+                .horizontal_alignment(alignment::Horizontal::Center),
+        )
+        .on_press_maybe(
+            self.diagnostics
+                .is_some()
+                .then_some(Message::ShowDiagnostics),
+        )
+        .padding([
+            design::button_padding_v(self.scale_factor),
+            design::button_padding_h(self.scale_factor),
+        ])
+        .width(Length::Fill)
+        .style(iced::theme::Button::Custom(Box::new(
+            theme::SecondaryButton(colors),
+        )));
+
+        let back_button = button(
+            text("Back to Settings")
+                .size(design::input_text_size(self.scale_factor))
+                .horizontal_alignment(alignment::Horizontal::Center),
+        )
+        .on_press(Message::CloseDiagnostics)
+        .padding([
+            design::button_padding_v(self.scale_factor),
+            design::button_padding_h(self.scale_factor),
+        ])
+        .width(Length::Fill)
+        .style(iced::theme::Button::Custom(Box::new(theme::PrimaryButton(
+            colors,
+        ))));
+
+        container(
+            column![
+                title_section,
+                scrollable(column(checks).spacing(design::small_space(self.scale_factor) as u16))
+                    .height(Length::Fill),
+                row![
+                    container(rerun_button).width(Length::FillPortion(1)),
+                    container(back_button).width(Length::FillPortion(2)),
+                ]
+                .spacing(design::small_space(self.scale_factor) as u16),
+            ]
+            .spacing(section_spacing),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(iced::theme::Container::Custom(Box::new(
+            theme::ContainerStyle(colors),
+        )))
+        .into()
+    }
+
+    // Check whether the chosen hardware encoder can be used, so the settings
+    // view can warn before recording falls back to software
+    fn refresh_hardware_status(&mut self) {
+        self.hardware_status = detect(
+            self.config.encoder_backend,
+            self.config.codec,
+            self.config.hardware_device.as_deref(),
+            Path::new(DEV_DIR),
+        )
+        .err()
+        .map(|reason| format!("{reason}, software encoding will be used"));
+    }
+
+    // Source the level meter listens to. With both recorded it follows the
+    // microphone, since that is the one that goes silent unnoticed.
+    fn meter_device(&self) -> Option<String> {
+        let (kind, preferred, fallback) = match self.config.audio {
+            AudioSource::None => return None,
+            AudioSource::System => (
+                AudioSource::System,
+                self.config.system_audio_device.as_deref(),
+                "@DEFAULT_MONITOR@",
+            ),
+            AudioSource::Microphone | AudioSource::Both => (
+                AudioSource::Microphone,
+                self.config.microphone_device.as_deref(),
+                "@DEFAULT_SOURCE@",
+            ),
+        };
+        Some(
+            self.audio_sources
+                .resolve(kind, preferred)
+                .unwrap_or_else(|| fallback.to_string()),
+        )
+    }
+
+    fn level_meter(&self, width: Length, height: f32) -> Element<'_, Message> {
+        progress_bar(0.0..=1.0, self.audio_level.meter())
+            .width(width)
+            .height(Length::Fixed(height))
+            .style(iced::theme::ProgressBar::Custom(Box::new(
+                theme::LevelMeter(self.theme.colors, self.audio_level.is_clipping()),
+            )))
+            .into()
+    }
+
+    // Picker for the device `kind` is recorded from
+    fn audio_device_picker(&self, kind: AudioSource) -> Element<'_, Message> {
+        let devices: Vec<AudioDevice> = self
+            .audio_sources
+            .devices_for(kind)
+            .into_iter()
+            .cloned()
+            .collect();
+        let (preferred, placeholder) = match kind {
+            AudioSource::System => (
+                self.config.system_audio_device.as_deref(),
+                "No monitor source",
+            ),
+            _ => (self.config.microphone_device.as_deref(), "No input source"),
+        };
+        let selected = self
+            .audio_sources
+            .resolve(kind, preferred)
+            .and_then(|name| devices.iter().find(|device| device.name == name).cloned());
+        let container_padding = design::container_padding(self.scale_factor);
+
+        container(
+            pick_list(devices, selected, Message::AudioDeviceSelected)
+                .placeholder(placeholder)
+                .padding([container_padding, container_padding])
+                .width(Length::Fill)
+                .text_size(design::input_text_size(self.scale_factor)),
+        )
+        .width(Length::FillPortion(1))
+        .style(iced::theme::Container::Custom(Box::new(theme::CardStyle(
+            self.theme.colors,
+        ))))
+        .into()
+    }
+
+    // Caches the next file name and the free space for the settings view,
+    // which is redrawn far too often to scan the directory every time
+    fn refresh_save_location(&mut self) {
+        self.filename_preview = self.next_filename().map_err(|e| format!("{e:#}"));
+        self.output_dir_free = free_space(&self.config.output_dir).ok();
+    }
+
+    // File name the next recording gets with the current template
+    fn next_filename(&self) -> Result<String> {
+        let template = FilenameTemplate::parse(&self.config.filename_template)?;
+        let context = FilenameContext {
+            kind: "recording",
+            time: Local::now(),
+            format: self.config.format,
+            region: &self.config.region,
+            profile: self.config.quality,
+        };
+        let suffix = part_suffix(self.config.split_minutes.map(|_| 1));
+        let extension = self.config.format.extension();
+        let name = template.unique_name(&self.config.output_dir, &context, &suffix, extension);
+        Ok(format!("{name}{suffix}.{extension}"))
+    }
+
+    // Updates `disk_space` and describes why recording has to stop, if it does
+    fn watch_disk_space(&mut self) -> Option<String> {
+        if !matches!(
+            self.state,
+            AppState::CompactRecording | AppState::CompactPaused | AppState::CompactBuffering
+        ) {
+            self.disk_space = DiskSpace::Plenty;
+            return None;
+        }
+
+        let dir = self.recorder.as_ref().and_then(Recorder::output_dir)?;
+        self.disk_space = self.config.disk_watchdog().check(dir);
+        match self.disk_space {
+            DiskSpace::Exhausted(free) => Some(format!(
+                "Recording stopped with only {} MB left on the disk",
+                free / (1024 * 1024)
+            )),
+            DiskSpace::Plenty | DiskSpace::Low(_) => None,
+        }
+    }
+
+    // Describes the configured limit the recording has reached, if any
+    fn limit_reached(&self) -> Option<String> {
+        if self.state != AppState::CompactRecording {
+            return None;
+        }
+
+        if let Some(limit) = self.config.max_duration() {
+            if self.recording_duration >= limit {
+                return Some(format!(
+                    "Recording stopped after reaching the {} minute limit",
+                    limit.as_secs() / 60
+                ));
+            }
+        }
+        if let (Some(limit), Some(recorder)) = (self.config.max_file_size(), &self.recorder) {
+            if recorder.recorded_bytes() >= limit {
+                return Some(format!(
+                    "Recording stopped after reaching the {} MB limit",
+                    limit / (1024 * 1024)
+                ));
+            }
+        }
+        None
+    }
+
+    // Shrink to the compact bar and count down to the recording
+    fn start_countdown(&mut self) -> Command<Message> {
+        self.error = None;
+        self.notice = None;
+        self.disk_space = DiskSpace::Plenty;
+
+        // Better to refuse now than to have wf-recorder fail after the countdown
+        let checked = FilenameTemplate::parse(&self.config.filename_template)
+            .and_then(|_| preflight(&self.config.output_dir, self.config.min_free_space()));
+        if let Err(e) = checked {
+            self.error = Some(format!("{e:#}"));
+            return Command::none();
+        }
+
+        // Always use compact mode for recording - non-intrusive
+        self.state = AppState::CompactCountdown(3);
+
+        // The window may have been moved to another monitor since startup
+        self.screen = Self::detect_screen();
+        self.scale_factor = design::scale_factor(self.screen.width, self.screen.height);
+
+        // Handle region selection vs fullscreen
+        let delay = match self.config.region {
+            CaptureRegion::Selection => 100,
+            CaptureRegion::FullScreen | CaptureRegion::Output(_) => 1000,
+        };
+
+        // Always switch to compact mode for recording
+        let compact_size = self.get_compact_size();
+        let position = self.get_compact_position();
+
+        Command::batch([
+            window::resize(window::Id::MAIN, compact_size),
+            window::move_to(window::Id::MAIN, position),
+            Command::perform(
+                async move {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                },
+                |_| Message::Tick,
+            ),
+        ])
+    }
+
+    // Carries out a control socket request, or says why it cannot be
+    fn handle_control(&mut self, request: control::Request) -> (Command<Message>, Option<String>) {
+        let refuse = |error: &str| (Command::none(), Some(String::from(error)));
+        match (request, self.state) {
+            (control::Request::Status, _) => (Command::none(), None),
+            (control::Request::SetProfile { profile }, _) => {
+                (self.update(Message::QualitySelected(profile)), None)
+            }
+            (
+                control::Request::Start,
+                AppState::Settings | AppState::RecordingFailed | AppState::Diagnostics,
+            ) => {
+                self.failure = None;
+                self.replay_requested = false;
+                let command = self.start_countdown();
+                match &self.error {
+                    Some(error) => (command, Some(error.clone())),
+                    None => (command, None),
+                }
+            }
+            (control::Request::Start, AppState::CompactPaused) => {
+                let command = self.update(Message::ResumeRecording);
+                // Failures end up in the recorder errors
+                let refused = (self.state == AppState::CompactPaused)
+                    .then(|| self.recorder_errors.last().cloned().unwrap_or_default());
+                (command, refused)
+            }
+            (control::Request::Start, _) => refuse("Already recording"),
+            (
+                control::Request::Stop,
+                AppState::CompactCountdown(_)
+                | AppState::CompactRecording
+                | AppState::CompactPaused
+                | AppState::CompactBuffering,
+            ) => (self.update(Message::StopRecording), None),
+            (control::Request::Stop, AppState::CompactStopping) => refuse("Already stopping"),
+            (control::Request::Stop, _) => refuse("Not recording"),
+            (control::Request::Pause, AppState::CompactRecording) => {
+                let command = self.update(Message::PauseRecording);
+                let refused = (self.state != AppState::CompactPaused)
+                    .then(|| self.recorder_errors.last().cloned().unwrap_or_default());
+                (command, refused)
+            }
+            (control::Request::Pause, AppState::CompactPaused) => refuse("Already paused"),
+            (control::Request::Pause, AppState::CompactBuffering) => {
+                refuse("The replay buffer cannot be paused")
+            }
+            (control::Request::Pause, _) => refuse("Not recording"),
+            (control::Request::Focus, _) => (
+                Command::batch([
+                    window::minimize(window::Id::MAIN, false),
+                    window::gain_focus(window::Id::MAIN),
+                ]),
+                None,
+            ),
+        }
+    }
+
+    // State reported to control socket clients
+    fn control_status(&self) -> control::Status {
+        let state = match self.state {
+            AppState::Settings | AppState::RecordingFailed | AppState::Diagnostics => "idle",
+            AppState::CompactCountdown(_) => "countdown",
+            AppState::CompactRecording => "recording",
+            AppState::CompactPaused => "paused",
+            AppState::CompactBuffering => "buffering",
+            AppState::CompactStopping => "stopping",
+        };
+        control::Status {
+            state,
+            profile: control::profile_name(self.config.quality),
+            elapsed_secs: self.recording_duration.as_secs(),
+            file: self
+                .recorder
+                .as_ref()
+                .and_then(Recorder::output_file)
+                .map(PathBuf::from),
+        }
+    }
+
+    // Switch back to the settings view with the normal window size, centered
+    fn return_to_settings(&mut self) -> Command<Message> {
+        self.state = AppState::Settings;
+        // The last recording took its name and some of the space
+        self.refresh_save_location();
+        self.restore_window()
+    }
+
+    // Resize the window back to the settings size and center it
+    fn restore_window(&self) -> Command<Message> {
+        let settings_size = self.get_settings_size();
+        let center_position = self.get_center_position(settings_size);
+
+        Command::batch([
+            window::resize(window::Id::MAIN, settings_size),
+            window::move_to(window::Id::MAIN, center_position),
+        ])
+    }
+
     // Helper methods for window sizing and positioning
     fn get_settings_size(&self) -> Size {
         Size::new(
@@ -673,15 +2286,16 @@ impl App {
         let padding = design::COMPACT_WINDOW_PADDING * self.scale_factor;
 
         // Ensure the window stays within screen bounds
-        let x = (self.screen_size.width - compact_size.width - padding).max(0.0);
-        let y = padding;
+        let x =
+            (self.screen.x + self.screen.width - compact_size.width - padding).max(self.screen.x);
+        let y = self.screen.y + padding;
 
         Point::new(x, y)
     }
 
     fn get_center_position(&self, window_size: Size) -> Point {
-        let x = ((self.screen_size.width - window_size.width) / 2.0).max(0.0);
-        let y = ((self.screen_size.height - window_size.height) / 2.0).max(0.0);
+        let x = self.screen.x + ((self.screen.width - window_size.width) / 2.0).max(0.0);
+        let y = self.screen.y + ((self.screen.height - window_size.height) / 2.0).max(0.0);
 
         Point::new(x, y)
     }
@@ -698,12 +2312,95 @@ impl std::fmt::Display for OutputFormat {
     }
 }
 
+impl std::fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VideoCodec::H264 => write!(f, "H.264"),
+            VideoCodec::H265 => write!(f, "H.265 / HEVC"),
+            VideoCodec::Vp8 => write!(f, "VP8"),
+            VideoCodec::Vp9 => write!(f, "VP9"),
+            VideoCodec::Av1Svt => write!(f, "AV1 (SVT)"),
+            VideoCodec::Av1Aom => write!(f, "AV1 (libaom)"),
+            VideoCodec::Ffv1 => write!(f, "FFV1 - Lossless"),
+        }
+    }
+}
+
+impl std::fmt::Display for EncoderBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncoderBackend::Software => write!(f, "Software"),
+            EncoderBackend::Vaapi => write!(f, "VAAPI"),
+            EncoderBackend::Nvenc => write!(f, "NVENC"),
+        }
+    }
+}
+
+impl std::fmt::Display for QualityPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QualityPreset::Draft => write!(f, "Draft"),
+            QualityPreset::Balanced => write!(f, "Balanced"),
+            QualityPreset::High => write!(f, "High"),
+            QualityPreset::Lossless => write!(f, "Lossless"),
+            QualityPreset::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+impl std::fmt::Display for Framerate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}FPS", self.get())
+    }
+}
+
+impl std::fmt::Display for AudioDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.description.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}", self.description)
+        }
+    }
+}
+
+impl std::fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioCodec::Opus => write!(f, "Opus"),
+            AudioCodec::Vorbis => write!(f, "Vorbis"),
+            AudioCodec::Aac => write!(f, "AAC"),
+            AudioCodec::Mp3 => write!(f, "MP3"),
+            AudioCodec::Flac => write!(f, "FLAC - Lossless"),
+            AudioCodec::Pcm => write!(f, "PCM - Uncompressed"),
+        }
+    }
+}
+
+impl std::fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} kHz", self.get() as f32 / 1000.0)
+    }
+}
+
+impl std::fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleFormat::Auto => write!(f, "Auto"),
+            SampleFormat::S16 => write!(f, "16-bit"),
+            SampleFormat::S32 => write!(f, "32-bit"),
+            SampleFormat::Float => write!(f, "32-bit float"),
+        }
+    }
+}
+
 impl std::fmt::Display for AudioSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AudioSource::None => write!(f, "No Audio"),
             AudioSource::System => write!(f, "System Audio"),
             AudioSource::Microphone => write!(f, "Microphone"),
+            AudioSource::Both => write!(f, "System Audio + Microphone"),
         }
     }
 }
@@ -713,6 +2410,91 @@ impl std::fmt::Display for CaptureRegion {
         match self {
             CaptureRegion::FullScreen => write!(f, "Full Screen"),
             CaptureRegion::Selection => write!(f, "Select Region"),
+            CaptureRegion::Output(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use control::Request;
+
+    // Asks the app listening at `socket` from a client thread, once the
+    // listener is up
+    fn ask(socket: &Path, request: Request) -> tokio::task::JoinHandle<serde_json::Value> {
+        let socket = socket.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            for _ in 0..100 {
+                if let Ok(response) = control::request(&socket, &request) {
+                    return response;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            panic!("nothing listening on {}", socket.display());
+        })
+    }
+
+    // Hands `request` from a client to the app's control handler, as the
+    // subscription does, and returns what the client got back
+    async fn send(
+        app: &mut App,
+        requests: &mut (impl futures::Stream<Item = (Request, control::Reply)> + Unpin),
+        socket: &Path,
+        request: Request,
+    ) -> serde_json::Value {
+        let answer = ask(socket, request);
+        // The listener binds once polled, the client waits for that
+        let (request, reply) = requests.next().await.unwrap();
+        let _ = app.update(Message::Control(request, reply));
+        answer.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn control_requests_reach_the_app() {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-app-control-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        // Requests that would save the settings are left out, they go to
+        // the real config file
+        let config = Config {
+            output_dir: dir.join("missing"),
+            quality: QualityPreset::High,
+            ..Config::default()
+        };
+        let mut app = App::idle(
+            config,
+            Rectangle::new(Point::ORIGIN, Size::new(1920.0, 1080.0)),
+        );
+        let socket = dir.join("control.sock");
+        let mut requests = Box::pin(control::listen(socket.clone()));
+
+        let response = send(&mut app, &mut requests, &socket, Request::Status).await;
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["status"]["state"], "idle");
+        assert_eq!(response["status"]["profile"], "high");
+
+        for request in [Request::Stop, Request::Pause] {
+            let response = send(&mut app, &mut requests, &socket, request).await;
+            assert_eq!(response["ok"], false);
+            assert_eq!(response["error"], "Not recording");
         }
+
+        // Refused before the countdown, the save location does not exist
+        let response = send(&mut app, &mut requests, &socket, Request::Start).await;
+        assert_eq!(response["ok"], false);
+        assert!(
+            response["error"].as_str().unwrap().contains("missing"),
+            "{response}"
+        );
+        assert_eq!(response["status"]["state"], "idle");
+
+        let response = send(&mut app, &mut requests, &socket, Request::Focus).await;
+        assert_eq!(response["ok"], true);
+
+        drop(requests);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+
+use super::{is_valid_bitrate, OutputFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioCodec {
+    Opus,
+    Vorbis,
+    Aac,
+    Mp3,
+    Flac,
+    Pcm,
+}
+
+impl AudioCodec {
+    /// ffmpeg encoder name passed to wf-recorder with `-C`. PCM has one
+    /// encoder per sample format.
+    pub fn encoder(&self, format: SampleFormat) -> &'static str {
+        match (self, format) {
+            (AudioCodec::Opus, _) => "libopus",
+            (AudioCodec::Vorbis, _) => "libvorbis",
+            (AudioCodec::Aac, _) => "aac",
+            (AudioCodec::Mp3, _) => "libmp3lame",
+            (AudioCodec::Flac, _) => "flac",
+            (AudioCodec::Pcm, SampleFormat::S32) => "pcm_s32le",
+            (AudioCodec::Pcm, SampleFormat::Float) => "pcm_f32le",
+            (AudioCodec::Pcm, _) => "pcm_s16le",
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioCodec::Flac | AudioCodec::Pcm)
+    }
+
+    /// Sample rates out of `SampleRate::common` the encoder accepts.
+    pub fn sample_rates(&self) -> &'static [SampleRate] {
+        const ALL: &[SampleRate] = &SampleRate::common();
+        match self {
+            // libopus only runs at 48 kHz and its integer fractions
+            AudioCodec::Opus => &[SampleRate(48_000)],
+            // MPEG-1 Layer III tops out at 48 kHz
+            AudioCodec::Mp3 => &ALL[..2],
+            _ => ALL,
+        }
+    }
+
+    /// Sample formats the encoder accepts. `Auto` lets wf-recorder pick.
+    pub fn sample_formats(&self) -> &'static [SampleFormat] {
+        match self {
+            AudioCodec::Opus => &[SampleFormat::Auto, SampleFormat::S16, SampleFormat::Float],
+            AudioCodec::Vorbis | AudioCodec::Aac => &[SampleFormat::Auto, SampleFormat::Float],
+            AudioCodec::Flac => &[SampleFormat::Auto, SampleFormat::S16, SampleFormat::S32],
+            AudioCodec::Mp3 | AudioCodec::Pcm => SampleFormat::all(),
+        }
+    }
+
+    /// ffmpeg sample format name passed with `-X`, which has to be one the
+    /// encoder takes, planar or not.
+    pub fn sample_format_name(&self, format: SampleFormat) -> Option<&'static str> {
+        if !self.sample_formats().contains(&format) {
+            return None;
+        }
+        let planar = matches!(self, AudioCodec::Vorbis | AudioCodec::Aac | AudioCodec::Mp3);
+        match (format, planar) {
+            (SampleFormat::Auto, _) => None,
+            (SampleFormat::S16, false) => Some("s16"),
+            (SampleFormat::S16, true) => Some("s16p"),
+            (SampleFormat::S32, false) => Some("s32"),
+            (SampleFormat::S32, true) => Some("s32p"),
+            (SampleFormat::Float, false) => Some("flt"),
+            (SampleFormat::Float, true) => Some("fltp"),
+        }
+    }
+}
+
+/// Audio sample rate in Hz, passed to wf-recorder with `-R`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SampleRate(u32);
+
+impl SampleRate {
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Rates offered in the settings view.
+    pub const fn common() -> [SampleRate; 3] {
+        [Self(44_100), Self(48_000), Self(96_000)]
+    }
+}
+
+impl Default for SampleRate {
+    fn default() -> Self {
+        Self(48_000)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SampleFormat {
+    Auto,
+    S16,
+    S32,
+    Float,
+}
+
+impl SampleFormat {
+    pub fn all() -> &'static [SampleFormat] {
+        &[
+            SampleFormat::Auto,
+            SampleFormat::S16,
+            SampleFormat::S32,
+            SampleFormat::Float,
+        ]
+    }
+}
+
+/// How the audio track is encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub codec: AudioCodec,
+    pub sample_rate: SampleRate,
+    pub sample_format: SampleFormat,
+    /// Target bitrate for lossy codecs in ffmpeg notation, e.g. `160k`
+    pub bitrate: Option<String>,
+}
+
+impl AudioSettings {
+    /// These settings with anything `format` or the codec cannot take
+    /// replaced by its default.
+    pub fn for_format(&self, format: OutputFormat) -> Self {
+        let codec = if format.supports_audio(self.codec) {
+            self.codec
+        } else {
+            format.default_audio_codec()
+        };
+        let sample_rate = if codec.sample_rates().contains(&self.sample_rate) {
+            self.sample_rate
+        } else {
+            SampleRate::default()
+        };
+        let sample_format = if codec.sample_formats().contains(&self.sample_format) {
+            self.sample_format
+        } else {
+            SampleFormat::Auto
+        };
+
+        Self {
+            codec,
+            sample_rate,
+            sample_format,
+            bitrate: self.bitrate.clone(),
+        }
+    }
+
+    /// These settings after the container changed from `previous` to
+    /// `format`. A codec left at the default of `previous` moves on to the
+    /// default of `format`, so AAC from MP4 becomes FLAC in MKV; one picked
+    /// by hand is kept where `format` can carry it.
+    pub fn switch_format(&self, previous: OutputFormat, format: OutputFormat) -> Self {
+        let codec = if self.codec == previous.default_audio_codec() {
+            format.default_audio_codec()
+        } else {
+            self.codec
+        };
+        Self {
+            codec,
+            ..self.clone()
+        }
+        .for_format(format)
+    }
+
+    /// Encoder parameters, passed to wf-recorder as `-P key=value`.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        match self.bitrate.as_deref() {
+            Some(bitrate) if !self.codec.is_lossless() && is_valid_bitrate(bitrate) => {
+                vec![("b", bitrate.to_string())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            codec: AudioCodec::Aac,
+            sample_rate: SampleRate::default(),
+            sample_format: SampleFormat::Auto,
+            bitrate: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(codec: AudioCodec, rate: u32, format: SampleFormat) -> AudioSettings {
+        AudioSettings {
+            codec,
+            sample_rate: SampleRate(rate),
+            sample_format: format,
+            bitrate: Some(String::from("160k")),
+        }
+    }
+
+    #[test]
+    fn unsupported_codecs_fall_back_per_container() {
+        let cases = [
+            (AudioCodec::Aac, OutputFormat::WebM, AudioCodec::Opus),
+            (AudioCodec::Vorbis, OutputFormat::WebM, AudioCodec::Vorbis),
+            (AudioCodec::Flac, OutputFormat::Mp4, AudioCodec::Aac),
+            (AudioCodec::Mp3, OutputFormat::Mp4, AudioCodec::Mp3),
+            (AudioCodec::Pcm, OutputFormat::Mkv, AudioCodec::Pcm),
+            (AudioCodec::Aac, OutputFormat::Mkv, AudioCodec::Aac),
+        ];
+        for (codec, format, expected) in cases {
+            let settings = settings(codec, 48_000, SampleFormat::Auto).for_format(format);
+            assert_eq!(settings.codec, expected, "{codec:?} in {format:?}");
+        }
+    }
+
+    #[test]
+    fn unsupported_rates_and_formats_fall_back() {
+        // libopus runs at 48 kHz only, and takes no 32-bit samples
+        let opus =
+            settings(AudioCodec::Opus, 44_100, SampleFormat::S32).for_format(OutputFormat::WebM);
+        assert_eq!(opus.sample_rate, SampleRate(48_000));
+        assert_eq!(opus.sample_format, SampleFormat::Auto);
+
+        // Leaving WebM for MP4 replaces Vorbis, and the float format goes
+        // along to AAC
+        let aac =
+            settings(AudioCodec::Vorbis, 96_000, SampleFormat::Float).for_format(OutputFormat::Mp4);
+        assert_eq!(aac.codec, AudioCodec::Aac);
+        assert_eq!(aac.sample_rate, SampleRate(96_000));
+        assert_eq!(aac.sample_format, SampleFormat::Float);
+
+        let mp3 =
+            settings(AudioCodec::Mp3, 96_000, SampleFormat::S16).for_format(OutputFormat::Mkv);
+        assert_eq!(mp3.sample_rate, SampleRate(48_000));
+        assert_eq!(mp3.sample_format, SampleFormat::S16);
+
+        let flac =
+            settings(AudioCodec::Flac, 96_000, SampleFormat::Float).for_format(OutputFormat::Mkv);
+        assert_eq!(flac.sample_rate, SampleRate(96_000));
+        assert_eq!(flac.sample_format, SampleFormat::Auto);
+        assert_eq!(flac.bitrate.as_deref(), Some("160k"));
+    }
+
+    #[test]
+    fn default_codec_follows_the_container() {
+        let mp4 = AudioSettings::default();
+        let mkv = mp4.switch_format(OutputFormat::Mp4, OutputFormat::Mkv);
+        assert_eq!(mkv.codec, AudioCodec::Flac);
+        let webm = mkv.switch_format(OutputFormat::Mkv, OutputFormat::WebM);
+        assert_eq!(webm.codec, AudioCodec::Opus);
+        let mp4 = webm.switch_format(OutputFormat::WebM, OutputFormat::Mp4);
+        assert_eq!(mp4.codec, AudioCodec::Aac);
+
+        // Picked by hand, so kept while the container can carry it
+        let picked = settings(AudioCodec::Opus, 48_000, SampleFormat::Auto);
+        assert_eq!(
+            picked
+                .switch_format(OutputFormat::Mp4, OutputFormat::Mkv)
+                .codec,
+            AudioCodec::Opus
+        );
+        let picked = settings(AudioCodec::Pcm, 48_000, SampleFormat::Auto);
+        assert_eq!(
+            picked
+                .switch_format(OutputFormat::Mkv, OutputFormat::Mp4)
+                .codec,
+            AudioCodec::Aac
+        );
+    }
+
+    #[test]
+    fn sample_format_names() {
+        let cases = [
+            (AudioCodec::Opus, SampleFormat::S16, Some("s16")),
+            (AudioCodec::Opus, SampleFormat::Float, Some("flt")),
+            (AudioCodec::Aac, SampleFormat::Float, Some("fltp")),
+            (AudioCodec::Vorbis, SampleFormat::Float, Some("fltp")),
+            (AudioCodec::Mp3, SampleFormat::S16, Some("s16p")),
+            (AudioCodec::Mp3, SampleFormat::S32, Some("s32p")),
+            (AudioCodec::Flac, SampleFormat::S32, Some("s32")),
+            (AudioCodec::Pcm, SampleFormat::Float, Some("flt")),
+            // Left to wf-recorder, or not one the encoder takes
+            (AudioCodec::Pcm, SampleFormat::Auto, None),
+            (AudioCodec::Aac, SampleFormat::S16, None),
+            (AudioCodec::Flac, SampleFormat::Float, None),
+        ];
+        for (codec, format, expected) in cases {
+            assert_eq!(
+                codec.sample_format_name(format),
+                expected,
+                "{codec:?} {format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn pcm_encoder_follows_the_sample_format() {
+        assert_eq!(AudioCodec::Pcm.encoder(SampleFormat::Auto), "pcm_s16le");
+        assert_eq!(AudioCodec::Pcm.encoder(SampleFormat::S32), "pcm_s32le");
+        assert_eq!(AudioCodec::Pcm.encoder(SampleFormat::Float), "pcm_f32le");
+        assert_eq!(AudioCodec::Flac.encoder(SampleFormat::S32), "flac");
+    }
+
+    #[test]
+    fn bitrate_only_for_lossy_codecs() {
+        let params = |codec, bitrate: Option<&str>| {
+            AudioSettings {
+                bitrate: bitrate.map(String::from),
+                ..settings(codec, 48_000, SampleFormat::Auto)
+            }
+            .params()
+        };
+        assert_eq!(
+            params(AudioCodec::Opus, Some("128k")),
+            vec![("b", String::from("128k"))]
+        );
+        assert_eq!(
+            params(AudioCodec::Aac, Some("192000")),
+            vec![("b", String::from("192000"))]
+        );
+        assert!(params(AudioCodec::Flac, Some("128k")).is_empty());
+        assert!(params(AudioCodec::Pcm, Some("128k")).is_empty());
+        assert!(params(AudioCodec::Opus, None).is_empty());
+        assert!(params(AudioCodec::Opus, Some("fast")).is_empty());
+    }
+}
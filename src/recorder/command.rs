@@ -0,0 +1,405 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use super::{
+    encoder_params, AudioSource, CaptureRegion, EncoderBackend, HardwareDevice, RecordingConfig,
+};
+
+/// Argument list for a single wf-recorder invocation.
+///
+/// Everything that needs the outside world (the slurp geometry, the pactl
+/// device) is resolved beforehand and handed in, so building the arguments is
+/// a pure function of its inputs.
+#[derive(Debug, Clone)]
+pub struct WfRecorderCommand {
+    config: RecordingConfig,
+    output_file: PathBuf,
+    geometry: Option<String>,
+    output: Option<String>,
+    audio_device: Option<String>,
+    hardware: Option<HardwareDevice>,
+}
+
+impl WfRecorderCommand {
+    pub fn new(config: RecordingConfig, output_file: PathBuf) -> Self {
+        Self {
+            config,
+            output_file,
+            geometry: None,
+            output: None,
+            audio_device: None,
+            hardware: None,
+        }
+    }
+
+    /// File to record into, replacing the one given to `new`.
+    pub fn output_file(mut self, output_file: PathBuf) -> Self {
+        self.output_file = output_file;
+        self
+    }
+
+    /// Region to capture, in slurp's `x,y wxh` format.
+    pub fn geometry(mut self, geometry: impl Into<String>) -> Self {
+        self.geometry = Some(geometry.into());
+        self
+    }
+
+    /// Monitor to capture for full screen recordings.
+    pub fn output(mut self, output: impl Into<String>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// Audio device to record from instead of the default one.
+    pub fn audio_device(mut self, device: impl Into<String>) -> Self {
+        self.audio_device = Some(device.into());
+        self
+    }
+
+    /// GPU to encode on; codecs it cannot handle stay on the software encoder.
+    pub fn hardware(mut self, device: HardwareDevice) -> Self {
+        self.hardware = Some(device);
+        self
+    }
+
+    pub fn args(&self) -> Vec<OsString> {
+        let hardware = self.hardware.as_ref().and_then(|device| {
+            let encoder = device.encoder(self.config.codec)?;
+            Some((device, encoder))
+        });
+        let (encoder, backend) = match hardware {
+            Some((device, encoder)) => (encoder, device.backend()),
+            None => (self.config.codec.encoder(), EncoderBackend::Software),
+        };
+
+        let mut args: Vec<OsString> = vec![
+            "-f".into(),
+            self.output_file.clone().into(),
+            "--codec".into(),
+            encoder.into(),
+            "-r".into(),
+            self.config.framerate.get().to_string().into(),
+        ];
+
+        if let Some((HardwareDevice::Vaapi(node), _)) = hardware {
+            args.push("-d".into());
+            args.push(node.clone().into());
+        }
+
+        for (key, value) in encoder_params(
+            self.config.quality,
+            &self.config.custom_quality,
+            self.config.codec,
+            backend,
+        ) {
+            args.push("-p".into());
+            args.push(format!("{key}={value}").into());
+        }
+
+        // wf-recorder takes the audio device as an optional argument, which
+        // getopt only accepts when attached to the flag itself
+        let audio = match (self.config.audio, &self.audio_device) {
+            (AudioSource::None, _) => false,
+            (_, Some(device)) => {
+                args.push(format!("--audio={device}").into());
+                true
+            }
+            // wf-recorder records from the default source; `Recorder` refuses
+            // to start when the device it needs cannot be found
+            (_, None) => {
+                args.push("-a".into());
+                true
+            }
+        };
+        if audio {
+            args.extend(self.audio_args());
+        }
+
+        match (&self.config.region, &self.geometry, &self.output) {
+            (CaptureRegion::Selection, Some(geometry), _) => {
+                args.push("-g".into());
+                args.push(geometry.into());
+            }
+            (CaptureRegion::Output(output), _, _)
+            | (CaptureRegion::FullScreen, _, Some(output)) => {
+                args.push("-o".into());
+                args.push(output.into());
+            }
+            _ => {}
+        }
+
+        args
+    }
+
+    // Audio encoder flags, checked against the container
+    fn audio_args(&self) -> Vec<OsString> {
+        let settings = self.config.audio_settings.for_format(self.config.format);
+        let codec = settings.codec;
+
+        let mut args: Vec<OsString> = vec![
+            "-C".into(),
+            codec.encoder(settings.sample_format).into(),
+            "-R".into(),
+            settings.sample_rate.get().to_string().into(),
+        ];
+
+        if let Some(name) = codec.sample_format_name(settings.sample_format) {
+            args.push("-X".into());
+            args.push(name.into());
+        }
+
+        for (key, value) in settings.params() {
+            args.push("-P".into());
+            args.push(format!("{key}={value}").into());
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::{
+        AudioCodec, AudioSettings, CustomQuality, Framerate, OutputFormat, QualityPreset,
+        SampleFormat, SampleRate, VideoCodec, DEFAULT_FILENAME_TEMPLATE,
+    };
+
+    fn config(format: OutputFormat, audio: AudioSource, region: CaptureRegion) -> RecordingConfig {
+        RecordingConfig {
+            format,
+            codec: format.default_codec(),
+            quality: QualityPreset::Balanced,
+            custom_quality: CustomQuality::default(),
+            encoder_backend: EncoderBackend::Software,
+            hardware_device: None,
+            audio,
+            system_audio_device: None,
+            microphone_device: None,
+            audio_settings: AudioSettings {
+                codec: AudioCodec::Aac,
+                sample_rate: SampleRate::default(),
+                sample_format: SampleFormat::Float,
+                bitrate: Some(String::from("160k")),
+            },
+            region,
+            framerate: Framerate::new(60).unwrap(),
+            output_dir: PathBuf::from("/videos"),
+            split_after: None,
+            filename_template: String::from(DEFAULT_FILENAME_TEMPLATE),
+            wf_recorder: PathBuf::from("wf-recorder"),
+        }
+    }
+
+    fn args(command: &WfRecorderCommand) -> Vec<String> {
+        command
+            .args()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn every_format_audio_and_region() {
+        // Video arguments follow from the container's default codec
+        let formats: [(OutputFormat, &[&str], &[&str]); 3] = [
+            (
+                OutputFormat::WebM,
+                &[
+                    "-f",
+                    "/videos/out.webm",
+                    "--codec",
+                    "libvpx",
+                    "-r",
+                    "60",
+                    "-p",
+                    "crf=24",
+                    "-p",
+                    "b=4M",
+                    "-p",
+                    "deadline=realtime",
+                    "-p",
+                    "cpu-used=6",
+                ],
+                // WebM cannot hold AAC, so Opus takes its place
+                &["-C", "libopus", "-R", "48000", "-X", "flt", "-P", "b=160k"],
+            ),
+            (
+                OutputFormat::Mp4,
+                &[
+                    "-f",
+                    "/videos/out.mp4",
+                    "--codec",
+                    "libx264",
+                    "-r",
+                    "60",
+                    "-p",
+                    "crf=26",
+                    "-p",
+                    "preset=veryfast",
+                ],
+                &["-C", "aac", "-R", "48000", "-X", "fltp", "-P", "b=160k"],
+            ),
+            (
+                OutputFormat::Mkv,
+                &[
+                    "-f",
+                    "/videos/out.mkv",
+                    "--codec",
+                    "libx264",
+                    "-r",
+                    "60",
+                    "-p",
+                    "crf=26",
+                    "-p",
+                    "preset=veryfast",
+                ],
+                &["-C", "aac", "-R", "48000", "-X", "fltp", "-P", "b=160k"],
+            ),
+        ];
+        let audio: [(AudioSource, Option<&str>, &[&str]); 4] = [
+            (AudioSource::None, None, &[]),
+            (
+                AudioSource::System,
+                Some("alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"),
+                &["--audio=alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"],
+            ),
+            (
+                AudioSource::Microphone,
+                Some("alsa_input.usb-Blue_Yeti-00.analog-stereo"),
+                &["--audio=alsa_input.usb-Blue_Yeti-00.analog-stereo"],
+            ),
+            (
+                AudioSource::Both,
+                Some("wf-recorder-gui-mix-42.monitor"),
+                &["--audio=wf-recorder-gui-mix-42.monitor"],
+            ),
+        ];
+        let regions: [(CaptureRegion, &[&str]); 3] = [
+            (CaptureRegion::FullScreen, &["-o", "DP-1"]),
+            (CaptureRegion::Selection, &["-g", "10,20 640x480"]),
+            (
+                CaptureRegion::Output(String::from("HDMI-A-1")),
+                &["-o", "HDMI-A-1"],
+            ),
+        ];
+
+        for (format, video_args, audio_args) in &formats {
+            for (source, device, source_args) in &audio {
+                for (region, region_args) in &regions {
+                    let output = PathBuf::from(format!("/videos/out.{}", format.extension()));
+                    let mut command =
+                        WfRecorderCommand::new(config(*format, *source, region.clone()), output)
+                            .geometry("10,20 640x480")
+                            .output("DP-1");
+                    if let Some(device) = device {
+                        command = command.audio_device(*device);
+                    }
+
+                    let mut expected = strings(video_args);
+                    expected.extend(strings(source_args));
+                    if *source != AudioSource::None {
+                        expected.extend(strings(audio_args));
+                    }
+                    expected.extend(strings(region_args));
+
+                    assert_eq!(
+                        args(&command),
+                        expected,
+                        "{format:?} with {source:?} audio, {region:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unresolved_parts_are_left_to_wf_recorder() {
+        // No device means the default source, no monitor lets wf-recorder pick
+        let command = WfRecorderCommand::new(
+            config(
+                OutputFormat::Mp4,
+                AudioSource::System,
+                CaptureRegion::FullScreen,
+            ),
+            PathBuf::from("/videos/out.mp4"),
+        );
+        assert_eq!(
+            args(&command),
+            strings(&[
+                "-f",
+                "/videos/out.mp4",
+                "--codec",
+                "libx264",
+                "-r",
+                "60",
+                "-p",
+                "crf=26",
+                "-p",
+                "preset=veryfast",
+                "-a",
+                "-C",
+                "aac",
+                "-R",
+                "48000",
+                "-X",
+                "fltp",
+                "-P",
+                "b=160k",
+            ])
+        );
+    }
+
+    #[test]
+    fn vaapi_passes_the_render_node() {
+        let command = WfRecorderCommand::new(
+            config(
+                OutputFormat::Mkv,
+                AudioSource::None,
+                CaptureRegion::Selection,
+            ),
+            PathBuf::from("/videos/out.mkv"),
+        )
+        .geometry("0,0 1920x1080")
+        .hardware(HardwareDevice::Vaapi(PathBuf::from("/dev/dri/renderD129")));
+        assert_eq!(
+            args(&command),
+            strings(&[
+                "-f",
+                "/videos/out.mkv",
+                "--codec",
+                "h264_vaapi",
+                "-r",
+                "60",
+                "-d",
+                "/dev/dri/renderD129",
+                "-p",
+                "rc_mode=CQP",
+                "-p",
+                "qp=26",
+                "-g",
+                "0,0 1920x1080",
+            ])
+        );
+    }
+
+    #[test]
+    fn codecs_without_a_hardware_encoder_stay_in_software() {
+        let mut config = config(
+            OutputFormat::Mkv,
+            AudioSource::None,
+            CaptureRegion::FullScreen,
+        );
+        config.codec = VideoCodec::Ffv1;
+        let command = WfRecorderCommand::new(config, PathBuf::from("/videos/out.mkv"))
+            .hardware(HardwareDevice::Vaapi(PathBuf::from("/dev/dri/renderD128")));
+        assert_eq!(
+            args(&command),
+            strings(&["-f", "/videos/out.mkv", "--codec", "ffv1", "-r", "60"])
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use super::RecorderExit;
+
+/// Structured view of what wf-recorder reports while it runs.
+#[derive(Debug, Clone)]
+pub enum RecorderEvent {
+    /// wf-recorder began capturing frames
+    Started,
+    /// The output or region wf-recorder settled on
+    OutputSelected(String),
+    /// Encoder details printed by wf-recorder or ffmpeg
+    EncoderInfo(String),
+    Warning(String),
+    Error(String),
+    /// wf-recorder exited, either after a stop request or on its own
+    Finished(RecorderExit),
+}
+
+impl RecorderEvent {
+    /// Classifies a single line of wf-recorder output.
+    ///
+    /// Returns `None` for lines that carry nothing worth surfacing, such as
+    /// blank lines or ffmpeg's stream dumps.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let lower = line.to_lowercase();
+
+        // ffmpeg log lines look like "[libx264 @ 0x5581c0a0] message"
+        if let Some(message) = ffmpeg_message(line) {
+            let message = message.to_string();
+            return Some(if is_error(&lower) {
+                RecorderEvent::Error(message)
+            } else if lower.contains("warning") {
+                RecorderEvent::Warning(message)
+            } else {
+                RecorderEvent::EncoderInfo(message)
+            });
+        }
+
+        if is_error(&lower) {
+            return Some(RecorderEvent::Error(line.to_string()));
+        }
+        if lower.contains("warning") {
+            return Some(RecorderEvent::Warning(line.to_string()));
+        }
+        // Before `Started`, which "recording output" would otherwise match
+        if let Some(selected) = strip_prefix_ignore_case(line, "selected output")
+            .or_else(|| strip_prefix_ignore_case(line, "selected region"))
+            .or_else(|| strip_prefix_ignore_case(line, "recording output"))
+        {
+            let selected = selected.trim_start_matches(':').trim();
+            return Some(RecorderEvent::OutputSelected(selected.to_string()));
+        }
+        if lower.starts_with("recording") {
+            return Some(RecorderEvent::Started);
+        }
+        if lower.starts_with("using") && (lower.contains("encoder") || lower.contains("codec")) {
+            return Some(RecorderEvent::EncoderInfo(line.to_string()));
+        }
+
+        None
+    }
+}
+
+fn ffmpeg_message(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('[')?;
+    let (tag, message) = rest.split_once(']')?;
+    tag.contains(" @ ").then(|| message.trim())
+}
+
+fn is_error(lower: &str) -> bool {
+    [
+        "error",
+        "failed",
+        "cannot",
+        "could not",
+        "unable to",
+        "permission denied",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle))
+}
+
+fn strip_prefix_ignore_case<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = line.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &line[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<RecorderEvent> {
+        RecorderEvent::parse(line)
+    }
+
+    #[test]
+    fn wf_recorder_lines() {
+        assert!(matches!(
+            parse("Recording..."),
+            Some(RecorderEvent::Started)
+        ));
+        assert!(matches!(
+            parse("selected region 0,0 1920x1080"),
+            Some(RecorderEvent::OutputSelected(region)) if region == "0,0 1920x1080"
+        ));
+        assert!(matches!(
+            parse("Selected output: DP-1"),
+            Some(RecorderEvent::OutputSelected(output)) if output == "DP-1"
+        ));
+        assert!(matches!(
+            parse("Recording output: HDMI-A-1"),
+            Some(RecorderEvent::OutputSelected(output)) if output == "HDMI-A-1"
+        ));
+        assert!(matches!(
+            parse("Using video encoder: libx264"),
+            Some(RecorderEvent::EncoderInfo(_))
+        ));
+        assert!(matches!(
+            parse("Failed to connect to the wayland display"),
+            Some(RecorderEvent::Error(_))
+        ));
+        assert!(matches!(
+            parse("compositor doesn't support wlr-screencopy-unstable-v1, cannot record"),
+            Some(RecorderEvent::Error(_))
+        ));
+    }
+
+    #[test]
+    fn ffmpeg_lines() {
+        assert!(matches!(
+            parse("[libx264 @ 0x5581c0a0e2c0] using cpu capabilities: MMX2 SSE2Fast SSSE3 SSE4.2 AVX FMA3 BMI2 AVX2"),
+            Some(RecorderEvent::EncoderInfo(message)) if message.starts_with("using cpu capabilities")
+        ));
+        assert!(matches!(
+            parse("[libx264 @ 0x5581c0a0e2c0] profile High, level 4.2, 4:2:0, 8-bit"),
+            Some(RecorderEvent::EncoderInfo(_))
+        ));
+        assert!(matches!(
+            parse("[h264_vaapi @ 0x55d3b5a0b640] No usable encoding entrypoint found for profile VAProfileH264High (7)."),
+            Some(RecorderEvent::EncoderInfo(_))
+        ));
+        assert!(matches!(
+            parse("[AVHWDeviceContext @ 0x55d3b59ef000] Failed to initialise VAAPI connection: -1 (unknown libva error)."),
+            Some(RecorderEvent::Error(message)) if message.starts_with("Failed to initialise")
+        ));
+        assert!(matches!(
+            parse("[mp4 @ 0x5623e2a4c880] Warning: the track time base is not set"),
+            Some(RecorderEvent::Warning(_))
+        ));
+    }
+
+    #[test]
+    fn noise_is_ignored() {
+        assert!(parse("").is_none());
+        assert!(parse("   ").is_none());
+        assert!(
+            parse("  Stream #0:0: Video: h264 (libx264), yuv420p, 1920x1080, q=2-31").is_none()
+        );
+        assert!(parse("Output #0, matroska, to 'recording.mkv':").is_none());
+    }
+}
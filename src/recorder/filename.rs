@@ -0,0 +1,388 @@
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use super::{CaptureRegion, OutputFormat, QualityPreset};
+
+/// Template that reproduces the original `recording_20240101_120000` names.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{kind}_{date}_{time}";
+
+/// What a recording's file name is built from.
+#[derive(Debug, Clone)]
+pub struct FilenameContext<'a> {
+    /// `recording` or `replay`
+    pub kind: &'a str,
+    pub time: DateTime<Local>,
+    pub format: OutputFormat,
+    pub region: &'a CaptureRegion,
+    pub profile: QualityPreset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Kind,
+    Date,
+    Time,
+    Format,
+    Output,
+    Region,
+    Profile,
+    Counter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    // Text that may hold chrono specifiers such as `%Y`
+    Literal(String),
+    Token(Token),
+}
+
+/// A file name pattern such as `{kind}_{date}_{time}` or `clip-%F-{counter}`.
+///
+/// Tokens in braces are replaced by the recording's details, everything else
+/// goes through chrono's strftime so `%Y`, `%H` and friends work anywhere.
+/// `{{` and `}}` stand for literal braces. The extension is added separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilenameTemplate {
+    pieces: Vec<Piece>,
+}
+
+impl FilenameTemplate {
+    /// Tokens the template understands, for listing in the settings.
+    pub const TOKENS: &'static [&'static str] = &[
+        "{kind}",
+        "{date}",
+        "{time}",
+        "{format}",
+        "{output}",
+        "{region}",
+        "{profile}",
+        "{counter}",
+    ];
+
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        anyhow::bail!("Unclosed '{{' in the filename template");
+                    };
+                    let token = match &rest[..end] {
+                        "kind" => Token::Kind,
+                        "date" => Token::Date,
+                        "time" => Token::Time,
+                        "format" => Token::Format,
+                        "output" => Token::Output,
+                        "region" => Token::Region,
+                        "profile" => Token::Profile,
+                        "counter" => Token::Counter,
+                        name => anyhow::bail!("Unknown token {{{name}}} in the filename template"),
+                    };
+                    chars = rest[end + 1..].chars();
+
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Token(token));
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in the filename template"),
+                '/' => anyhow::bail!("The filename template cannot contain '/'"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        if pieces.is_empty() {
+            anyhow::bail!("The filename template is empty");
+        }
+        for piece in &pieces {
+            if let Piece::Literal(text) = piece {
+                if StrftimeItems::new(text).any(|item| item == Item::Error) {
+                    anyhow::bail!("Invalid date specifier in the filename template");
+                }
+            }
+        }
+
+        Ok(Self { pieces })
+    }
+
+    /// Whether the template numbers files itself through `{counter}`.
+    pub fn uses_counter(&self) -> bool {
+        self.pieces.contains(&Piece::Token(Token::Counter))
+    }
+
+    /// The file name without its extension, with `{counter}` set to
+    /// `counter`.
+    pub fn render(&self, context: &FilenameContext, counter: u32) -> String {
+        let mut name = String::new();
+        for piece in &self.pieces {
+            match piece {
+                // Specifiers were checked by `parse`, so this cannot fail
+                Piece::Literal(text) => {
+                    let _ = write!(name, "{}", context.time.format(text));
+                }
+                Piece::Token(token) => name.push_str(&token_value(*token, context, counter)),
+            }
+        }
+        // Dates like %D would otherwise create directories
+        name.replace('/', "-")
+    }
+
+    /// A name for a file in `dir` that does not exist yet. `suffix` is what
+    /// follows the name before the extension, such as a part number.
+    ///
+    /// Templates with `{counter}` take the first free number, others get
+    /// `_2`, `_3` and so on appended when the plain name is taken.
+    pub fn unique_name(
+        &self,
+        dir: &Path,
+        context: &FilenameContext,
+        suffix: &str,
+        extension: &str,
+    ) -> String {
+        let taken = |name: &str| dir.join(format!("{name}{suffix}.{extension}")).exists();
+
+        if self.uses_counter() {
+            return (1..)
+                .map(|counter| self.render(context, counter))
+                .find(|name| !taken(name))
+                .expect("ran out of counter values");
+        }
+
+        let name = self.render(context, 1);
+        if !taken(&name) {
+            return name;
+        }
+        (2..)
+            .map(|n| format!("{name}_{n}"))
+            .find(|name| !taken(name))
+            .expect("ran out of suffixes")
+    }
+}
+
+/// `path`, or the first of `<stem>_2.<ext>`, `<stem>_3.<ext>`, ... that does
+/// not exist yet, the way `unique_name` resolves collisions.
+pub fn unique_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| path.with_file_name(format!("{stem}_{n}{extension}")))
+        .find(|candidate| !candidate.exists())
+        .expect("ran out of suffixes")
+}
+
+/// `_part001` and so on for split recordings, nothing otherwise.
+pub fn part_suffix(part: Option<usize>) -> String {
+    part.map(|part| format!("_part{part:03}"))
+        .unwrap_or_default()
+}
+
+fn token_value(token: Token, context: &FilenameContext, counter: u32) -> String {
+    match token {
+        Token::Kind => context.kind.to_string(),
+        Token::Date => context.time.format("%Y%m%d").to_string(),
+        Token::Time => context.time.format("%H%M%S").to_string(),
+        Token::Format => context.format.extension().to_string(),
+        // Name of the monitor when recording one, otherwise what is captured
+        Token::Output => match context.region {
+            CaptureRegion::FullScreen => String::from("screen"),
+            CaptureRegion::Selection => String::from("region"),
+            CaptureRegion::Output(name) => name.clone(),
+        },
+        Token::Region => match context.region {
+            CaptureRegion::FullScreen => String::from("fullscreen"),
+            CaptureRegion::Selection => String::from("selection"),
+            CaptureRegion::Output(_) => String::from("output"),
+        },
+        Token::Profile => match context.profile {
+            QualityPreset::Draft => String::from("draft"),
+            QualityPreset::Balanced => String::from("balanced"),
+            QualityPreset::High => String::from("high"),
+            QualityPreset::Lossless => String::from("lossless"),
+            QualityPreset::Custom => String::from("custom"),
+        },
+        Token::Counter => format!("{counter:03}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+
+    fn context(region: &CaptureRegion) -> FilenameContext<'_> {
+        FilenameContext {
+            kind: "recording",
+            time: Local.with_ymd_and_hms(2026, 10, 16, 10, 15, 0).unwrap(),
+            format: OutputFormat::Mkv,
+            region,
+            profile: QualityPreset::Balanced,
+        }
+    }
+
+    fn render(template: &str) -> String {
+        FilenameTemplate::parse(template)
+            .unwrap()
+            .render(&context(&CaptureRegion::Output("DP-1".into())), 7)
+    }
+
+    // Fresh empty directory for collision tests
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-filename-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn default_template_keeps_the_original_names() {
+        assert_eq!(
+            render(DEFAULT_FILENAME_TEMPLATE),
+            "recording_20261016_101500"
+        );
+    }
+
+    #[test]
+    fn tokens_are_replaced() {
+        assert_eq!(
+            render("{output}-{region}-{profile}-{format}-{counter}"),
+            "DP-1-output-balanced-mkv-007"
+        );
+        let template = FilenameTemplate::parse("{output}_{region}").unwrap();
+        assert_eq!(
+            template.render(&context(&CaptureRegion::Selection), 1),
+            "region_selection"
+        );
+    }
+
+    #[test]
+    fn chrono_specifiers_are_expanded() {
+        assert_eq!(render("clip %Y-%m-%d %Hh%M"), "clip 2026-10-16 10h15");
+        // Slashes from a date never turn into directories
+        assert_eq!(render("%D"), "10-16-26");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{{kind}}}"), "{recording}");
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for template in [
+            "",
+            "{kind",
+            "kind}",
+            "{unknown}",
+            "dir/{kind}",
+            "%Q",
+            "100%",
+        ] {
+            assert!(
+                FilenameTemplate::parse(template).is_err(),
+                "{template:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn taken_names_get_a_suffix() {
+        let dir = scratch_dir("suffix");
+        let region = CaptureRegion::FullScreen;
+        let template = FilenameTemplate::parse("{kind}").unwrap();
+
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mkv"),
+            "recording"
+        );
+        fs::write(dir.join("recording.mkv"), "").unwrap();
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mkv"),
+            "recording_2"
+        );
+        fs::write(dir.join("recording_2.mkv"), "").unwrap();
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mkv"),
+            "recording_3"
+        );
+
+        // Only files with the same extension and suffix collide
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mp4"),
+            "recording"
+        );
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "_part001", "mkv"),
+            "recording"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn taken_paths_get_a_suffix() {
+        let dir = scratch_dir("path");
+        let part = dir.join("recording_part002.mkv");
+        assert_eq!(unique_path(part.clone()), part);
+
+        fs::write(&part, "").unwrap();
+        assert_eq!(
+            unique_path(part.clone()),
+            dir.join("recording_part002_2.mkv")
+        );
+        fs::write(dir.join("recording_part002_2.mkv"), "").unwrap();
+        assert_eq!(unique_path(part), dir.join("recording_part002_3.mkv"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn counter_takes_the_first_free_number() {
+        let dir = scratch_dir("counter");
+        let region = CaptureRegion::FullScreen;
+        let template = FilenameTemplate::parse("take-{counter}").unwrap();
+
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mkv"),
+            "take-001"
+        );
+        fs::write(dir.join("take-001.mkv"), "").unwrap();
+        fs::write(dir.join("take-002.mkv"), "").unwrap();
+        assert_eq!(
+            template.unique_name(&dir, &context(&region), "", "mkv"),
+            "take-003"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
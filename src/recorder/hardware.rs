@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::VideoCodec;
+
+/// Root of the device tree scanned for GPUs.
+pub const DEV_DIR: &str = "/dev";
+
+/// Which kind of encoder turns frames into video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EncoderBackend {
+    Software,
+    Vaapi,
+    Nvenc,
+}
+
+impl EncoderBackend {
+    pub fn all() -> &'static [EncoderBackend] {
+        &[
+            EncoderBackend::Software,
+            EncoderBackend::Vaapi,
+            EncoderBackend::Nvenc,
+        ]
+    }
+}
+
+/// A GPU that was found and can be handed to wf-recorder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HardwareDevice {
+    /// DRM render node, passed to wf-recorder with `-d`
+    Vaapi(PathBuf),
+    Nvenc,
+}
+
+impl HardwareDevice {
+    pub fn backend(&self) -> EncoderBackend {
+        match self {
+            HardwareDevice::Vaapi(_) => EncoderBackend::Vaapi,
+            HardwareDevice::Nvenc => EncoderBackend::Nvenc,
+        }
+    }
+
+    /// ffmpeg encoder for `codec` on this device, if it has one.
+    pub fn encoder(&self, codec: VideoCodec) -> Option<&'static str> {
+        match (self, codec) {
+            (HardwareDevice::Vaapi(_), VideoCodec::H264) => Some("h264_vaapi"),
+            (HardwareDevice::Vaapi(_), VideoCodec::H265) => Some("hevc_vaapi"),
+            (HardwareDevice::Vaapi(_), VideoCodec::Vp8) => Some("vp8_vaapi"),
+            (HardwareDevice::Vaapi(_), VideoCodec::Vp9) => Some("vp9_vaapi"),
+            (HardwareDevice::Vaapi(_), VideoCodec::Av1Svt | VideoCodec::Av1Aom) => {
+                Some("av1_vaapi")
+            }
+            (HardwareDevice::Nvenc, VideoCodec::H264) => Some("h264_nvenc"),
+            (HardwareDevice::Nvenc, VideoCodec::H265) => Some("hevc_nvenc"),
+            (HardwareDevice::Nvenc, VideoCodec::Av1Svt | VideoCodec::Av1Aom) => Some("av1_nvenc"),
+            _ => None,
+        }
+    }
+}
+
+/// DRM render nodes (`renderD128`, ...) under `dri_dir`, sorted by name.
+pub fn render_nodes(dri_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dri_dir) else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("renderD"))
+        })
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+/// Finds the device to encode `codec` on with `backend`.
+///
+/// `preferred` selects a render node by name; the first one found is used when
+/// it is unset or missing. Returns `Ok(None)` for software encoding and an
+/// explanation when a hardware backend was asked for but cannot be used.
+pub fn detect(
+    backend: EncoderBackend,
+    codec: VideoCodec,
+    preferred: Option<&Path>,
+    dev_dir: &Path,
+) -> Result<Option<HardwareDevice>, String> {
+    let device = match backend {
+        EncoderBackend::Software => return Ok(None),
+        EncoderBackend::Vaapi => {
+            let nodes = render_nodes(&dev_dir.join("dri"));
+            let preferred = preferred.and_then(|path| path.file_name());
+            let node = nodes
+                .iter()
+                .find(|node| node.file_name() == preferred)
+                .or_else(|| nodes.first())
+                .ok_or("No VAAPI render node found")?;
+            HardwareDevice::Vaapi(node.clone())
+        }
+        EncoderBackend::Nvenc => {
+            if !dev_dir.join("nvidiactl").exists() {
+                return Err(String::from("No NVIDIA device found"));
+            }
+            HardwareDevice::Nvenc
+        }
+    };
+
+    match device.encoder(codec) {
+        Some(_) => Ok(Some(device)),
+        None => Err(format!("{codec} has no {backend} encoder")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fake /dev holding the given files
+    fn dev_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-hardware-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("dri")).unwrap();
+        for file in files {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn render_nodes_are_sorted() {
+        let dev = dev_dir(
+            "nodes",
+            &["dri/renderD129", "dri/card0", "dri/renderD128", "dri/card1"],
+        );
+        assert_eq!(
+            render_nodes(&dev.join("dri")),
+            [dev.join("dri/renderD128"), dev.join("dri/renderD129")]
+        );
+        assert_eq!(render_nodes(&dev.join("missing")), Vec::<PathBuf>::new());
+        fs::remove_dir_all(&dev).unwrap();
+    }
+
+    #[test]
+    fn vaapi_prefers_the_chosen_node() {
+        let dev = dev_dir("vaapi", &["dri/renderD128", "dri/renderD129"]);
+        let detect_with = |preferred: Option<&str>| {
+            detect(
+                EncoderBackend::Vaapi,
+                VideoCodec::H264,
+                preferred.map(Path::new),
+                &dev,
+            )
+        };
+
+        let node = |name: &str| Ok(Some(HardwareDevice::Vaapi(dev.join("dri").join(name))));
+        assert_eq!(detect_with(None), node("renderD128"));
+        // Only the node name counts, the saved path may be from a different /dev
+        assert_eq!(detect_with(Some("/dev/dri/renderD129")), node("renderD129"));
+        // A GPU that has gone away falls back to the first one
+        assert_eq!(detect_with(Some("/dev/dri/renderD130")), node("renderD128"));
+
+        assert_eq!(
+            detect(EncoderBackend::Vaapi, VideoCodec::Ffv1, None, &dev),
+            Err(String::from("FFV1 - Lossless has no VAAPI encoder"))
+        );
+        fs::remove_dir_all(&dev).unwrap();
+    }
+
+    #[test]
+    fn empty_dev_dir() {
+        let dev = dev_dir("empty", &[]);
+        assert_eq!(
+            detect(EncoderBackend::Vaapi, VideoCodec::H264, None, &dev),
+            Err(String::from("No VAAPI render node found"))
+        );
+        assert_eq!(
+            detect(EncoderBackend::Nvenc, VideoCodec::H264, None, &dev),
+            Err(String::from("No NVIDIA device found"))
+        );
+        assert_eq!(
+            detect(EncoderBackend::Software, VideoCodec::H264, None, &dev),
+            Ok(None)
+        );
+        fs::remove_dir_all(&dev).unwrap();
+    }
+
+    #[test]
+    fn nvenc() {
+        let dev = dev_dir("nvenc", &["nvidiactl", "nvidia0"]);
+        assert_eq!(
+            detect(EncoderBackend::Nvenc, VideoCodec::H265, None, &dev),
+            Ok(Some(HardwareDevice::Nvenc))
+        );
+        assert_eq!(
+            detect(EncoderBackend::Nvenc, VideoCodec::Vp9, None, &dev),
+            Err(String::from("VP9 has no NVENC encoder"))
+        );
+        fs::remove_dir_all(&dev).unwrap();
+    }
+}
@@ -1,8 +1,32 @@
 use anyhow::{Context, Result};
 use chrono::Local;
+use futures::stream::{self, Stream};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdout};
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::audio::{self, AudioMix, Pactl};
+use crate::display;
+
+use super::{
+    detect, part_suffix, segments, unique_path, AudioCodec, AudioSettings, CustomQuality,
+    EncoderBackend, FilenameContext, FilenameTemplate, QualityPreset, RecorderEvent, ReplayBuffer,
+    ReplaySnapshot, WfRecorderCommand, DEV_DIR,
+};
+
+// How long wf-recorder gets to flush and finalize the file after SIGINT
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(10);
+// How long to wait after SIGTERM before falling back to SIGKILL
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(3);
+// Number of trailing stderr lines kept for error reports
+const STDERR_TAIL_LINES: usize = 10;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum OutputFormat {
@@ -20,7 +44,6 @@ impl OutputFormat {
         }
     }
 
-    #[allow(dead_code)]
     pub fn all() -> &'static [(OutputFormat, &'static str)] {
         &[
             (OutputFormat::WebM, "WebM - Best for web"),
@@ -29,92 +52,330 @@ impl OutputFormat {
         ]
     }
 
-    fn codec(&self) -> &'static str {
+    /// Video codecs this container can carry, in order of preference.
+    pub fn codecs(&self) -> &'static [VideoCodec] {
+        match self {
+            OutputFormat::WebM => &[
+                VideoCodec::Vp8,
+                VideoCodec::Vp9,
+                VideoCodec::Av1Svt,
+                VideoCodec::Av1Aom,
+            ],
+            OutputFormat::Mp4 => &[
+                VideoCodec::H264,
+                VideoCodec::H265,
+                VideoCodec::Av1Svt,
+                VideoCodec::Av1Aom,
+            ],
+            OutputFormat::Mkv => VideoCodec::all(),
+        }
+    }
+
+    pub fn default_codec(&self) -> VideoCodec {
+        self.codecs()[0]
+    }
+
+    pub fn supports(&self, codec: VideoCodec) -> bool {
+        self.codecs().contains(&codec)
+    }
+
+    /// Audio codecs this container can carry, in order of preference.
+    pub fn audio_codecs(&self) -> &'static [AudioCodec] {
+        match self {
+            OutputFormat::WebM => &[AudioCodec::Opus, AudioCodec::Vorbis],
+            OutputFormat::Mp4 => &[AudioCodec::Aac, AudioCodec::Opus, AudioCodec::Mp3],
+            OutputFormat::Mkv => &[
+                AudioCodec::Flac,
+                AudioCodec::Pcm,
+                AudioCodec::Opus,
+                AudioCodec::Vorbis,
+                AudioCodec::Aac,
+                AudioCodec::Mp3,
+            ],
+        }
+    }
+
+    pub fn default_audio_codec(&self) -> AudioCodec {
+        self.audio_codecs()[0]
+    }
+
+    pub fn supports_audio(&self, codec: AudioCodec) -> bool {
+        self.audio_codecs().contains(&codec)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp8,
+    Vp9,
+    Av1Svt,
+    Av1Aom,
+    Ffv1,
+}
+
+impl VideoCodec {
+    pub fn all() -> &'static [VideoCodec] {
+        &[
+            VideoCodec::H264,
+            VideoCodec::H265,
+            VideoCodec::Vp8,
+            VideoCodec::Vp9,
+            VideoCodec::Av1Svt,
+            VideoCodec::Av1Aom,
+            VideoCodec::Ffv1,
+        ]
+    }
+
+    /// ffmpeg encoder name passed to wf-recorder with `--codec`.
+    pub fn encoder(&self) -> &'static str {
         match self {
-            OutputFormat::WebM => "libvpx",
-            OutputFormat::Mp4 => "libx264",
-            OutputFormat::Mkv => "libx264",
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::Vp8 => "libvpx",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1Svt => "libsvtav1",
+            VideoCodec::Av1Aom => "libaom-av1",
+            VideoCodec::Ffv1 => "ffv1",
         }
     }
 }
 
+/// Constant frame rate passed to wf-recorder with `-r`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Framerate(u32);
+
+impl Framerate {
+    pub const MIN: u32 = 1;
+    pub const MAX: u32 = 240;
+
+    pub fn new(fps: u32) -> Option<Self> {
+        (Self::MIN..=Self::MAX).contains(&fps).then_some(Self(fps))
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    // Values read from disk bypass `new`, so they are checked separately
+    pub fn is_valid(self) -> bool {
+        Self::new(self.0).is_some()
+    }
+
+    /// Rates offered in the settings view.
+    pub fn common() -> [Framerate; 4] {
+        [Self(15), Self(24), Self(30), Self(60)]
+    }
+}
+
+impl Default for Framerate {
+    fn default() -> Self {
+        Self(30)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AudioSource {
     None,
     System,
     Microphone,
+    /// System audio and microphone mixed into one track
+    Both,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CaptureRegion {
     FullScreen,
     Selection,
+    /// A specific monitor, by connector name
+    Output(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct RecordingConfig {
     pub format: OutputFormat,
+    pub codec: VideoCodec,
+    pub quality: QualityPreset,
+    pub custom_quality: CustomQuality,
+    pub encoder_backend: EncoderBackend,
+    pub hardware_device: Option<PathBuf>,
     pub audio: AudioSource,
+    /// PulseAudio source names, defaults are used when unset
+    pub system_audio_device: Option<String>,
+    pub microphone_device: Option<String>,
+    pub audio_settings: AudioSettings,
     pub region: CaptureRegion,
+    pub framerate: Framerate,
     pub output_dir: PathBuf,
+    /// Recorded time after which the next part file is started, recordings
+    /// go into a single file when unset
+    pub split_after: Option<Duration>,
+    /// Pattern for output file names, see `FilenameTemplate`
+    pub filename_template: String,
+    /// Program run for each segment, wf-recorder as found on `PATH`
+    pub wf_recorder: PathBuf,
+}
+
+/// How wf-recorder ended, along with the last lines it wrote to stderr.
+#[derive(Debug, Clone)]
+pub struct RecorderExit {
+    pub status: ExitStatus,
+    pub stderr_tail: Vec<String>,
 }
 
-#[derive(Clone)]
+// Handle to a wf-recorder process owned by its supervisor task
+struct RecorderProcess {
+    pid: u32,
+    signals: mpsc::UnboundedSender<libc::c_int>,
+    events: Arc<Mutex<mpsc::UnboundedReceiver<RecorderEvent>>>,
+    exit: watch::Receiver<Option<RecorderExit>>,
+}
+
+/// A recording made of one wf-recorder run per segment. Pausing ends the
+/// current segment and resuming starts the next; `stop` joins them into the
+/// output file.
 pub struct Recorder {
     config: RecordingConfig,
-    pid: Option<u32>,
+    // Arguments resolved by `start`, reused for every segment
+    command: Option<WfRecorderCommand>,
+    output_file: Option<PathBuf>,
+    segments: Vec<PathBuf>,
+    process: Option<RecorderProcess>,
+    // Paused segments whose wf-recorder may still be finalizing its file
+    finishing: Vec<RecorderProcess>,
+    // Null sink combining system audio and microphone, if recording both
+    mix: Option<AudioMix>,
+    // Set when buffering a replay instead of recording to a file
+    replay: Option<ReplayBuffer>,
+    segment_started: Option<Instant>,
+    // File name shared by every part of a split recording
+    base_name: Option<String>,
+    // Number of the part being recorded when splitting
+    part: Option<usize>,
+    // Recorded time in the current part's ended segments
+    part_recorded: Duration,
+    // Bytes in the parts before the current one
+    parts_bytes: u64,
+    // Earlier parts being joined in the background
+    parts: Vec<JoinHandle<Result<()>>>,
 }
 
 impl Recorder {
     pub fn new(config: RecordingConfig) -> Self {
-        Self { config, pid: None }
+        Self {
+            config,
+            command: None,
+            output_file: None,
+            segments: Vec::new(),
+            process: None,
+            finishing: Vec::new(),
+            mix: None,
+            replay: None,
+            segment_started: None,
+            base_name: None,
+            part: None,
+            part_recorded: Duration::default(),
+            parts_bytes: 0,
+            parts: Vec::new(),
+        }
     }
 
-    fn generate_filename(&self) -> PathBuf {
-        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-        let mut path = self.config.output_dir.clone();
-        path.push(format!(
-            "recording_{}.{}",
-            timestamp,
+    // Picks an unused name from the filename template, the first part's
+    // name for split recordings
+    fn generate_filename(&self, kind: &str, part: Option<usize>) -> Result<(String, PathBuf)> {
+        let template = FilenameTemplate::parse(&self.config.filename_template)?;
+        let context = FilenameContext {
+            kind,
+            time: Local::now(),
+            format: self.config.format,
+            region: &self.config.region,
+            profile: self.config.quality,
+        };
+        let name = template.unique_name(
+            &self.config.output_dir,
+            &context,
+            &part_suffix(part),
+            self.config.format.extension(),
+        );
+        let path = self.part_filename(&name, part);
+        Ok((name, path))
+    }
+
+    fn part_filename(&self, name: &str, part: Option<usize>) -> PathBuf {
+        self.config.output_dir.join(format!(
+            "{}{}.{}",
+            name,
+            part_suffix(part),
             self.config.format.extension()
-        ));
-        path
+        ))
     }
 
     pub fn start(&mut self) -> Result<()> {
-        // Ensure wf-recorder is installed
-        which::which("wf-recorder").context("wf-recorder not found. Please install it first.")?;
+        self.part = self.config.split_after.map(|_| 1);
 
-        // Build base command
-        let mut cmd = Command::new("wf-recorder");
+        // Generate unique filename; wf-recorder writes segments next to it
+        let (name, output_file) = self.generate_filename("recording", self.part)?;
+        self.base_name = Some(name);
+        self.start_into(output_file)
+    }
 
-        // Generate unique filename
-        let output_file = self.generate_filename();
-        cmd.arg("-f").arg(&output_file);
+    /// Records continuously into `buffer`, keeping only its most recent
+    /// segments. `snapshot_replay` saves what is buffered.
+    pub fn start_replay(&mut self, buffer: ReplayBuffer) -> Result<()> {
+        buffer
+            .reset()
+            .with_context(|| format!("Failed to prepare {}", buffer.dir().display()))?;
+        let output_file = buffer.output_file(self.config.format.extension());
+        self.replay = Some(buffer);
+        self.start_into(output_file)
+    }
 
-        // Use software encoding by default
-        cmd.arg("--codec").arg(self.config.format.codec());
+    fn start_into(&mut self, output_file: PathBuf) -> Result<()> {
+        // Ensure wf-recorder is installed
+        which::which(&self.config.wf_recorder)
+            .context("wf-recorder not found. Please install it first.")?;
 
-        // Add audio configuration
-        match self.config.audio {
-            AudioSource::None => {}
-            AudioSource::System => {
-                cmd.arg("-a");
-            }
-            AudioSource::Microphone => {
-                // Get default mic
-                let output = Command::new("pactl")
-                    .args(["list", "sources", "short"])
-                    .output()?;
-                let sources = String::from_utf8_lossy(&output.stdout);
-                if let Some(mic) = sources.lines().next() {
-                    let mic_name = mic.split('\t').next().unwrap_or("");
-                    cmd.arg("-a").arg(mic_name);
+        let mut command = WfRecorderCommand::new(self.config.clone(), output_file.clone());
+
+        // Resolve the source to record from
+        if self.config.audio != AudioSource::None {
+            let sources = audio::Sources::query();
+            let system = sources.resolve(
+                AudioSource::System,
+                self.config.system_audio_device.as_deref(),
+            );
+            let microphone = sources.resolve(
+                AudioSource::Microphone,
+                self.config.microphone_device.as_deref(),
+            );
+
+            match (self.config.audio, system, microphone) {
+                (AudioSource::Both, Some(system), Some(microphone)) => {
+                    let mix = AudioMix::create(Pactl, &system, &microphone)
+                        .context("Failed to combine system audio and microphone")?;
+                    command = command.audio_device(mix.source());
+                    self.mix = Some(mix);
+                }
+                (AudioSource::Both, _, _) => {
+                    anyhow::bail!("Recording system audio and microphone needs both devices")
+                }
+                (AudioSource::System, Some(device), _)
+                | (AudioSource::Microphone, _, Some(device)) => {
+                    command = command.audio_device(device);
+                }
+                (AudioSource::System, None, _) => {
+                    anyhow::bail!("No system audio device found to record from")
+                }
+                (AudioSource::Microphone, _, None) => {
+                    anyhow::bail!("No microphone found to record from")
                 }
+                (AudioSource::None, _, _) => {}
             }
         }
 
-        // Add region selection if needed
+        // Let the user pick the region if needed
         if let CaptureRegion::Selection = self.config.region {
             // Check for slurp
             which::which("slurp")
@@ -125,32 +386,742 @@ impl Recorder {
                 .output()
                 .context("Failed to run slurp")?;
 
+            // slurp exits with an error and prints nothing when cancelled
             let geometry = String::from_utf8_lossy(&geometry.stdout);
             let geometry = geometry.trim();
+            if geometry.is_empty() {
+                anyhow::bail!("Region selection was cancelled");
+            }
 
-            cmd.arg("-g").arg(geometry);
+            command = command.geometry(geometry);
         }
 
-        // Start the recording process
-        let child = cmd.spawn().context("Failed to start wf-recorder")?;
-        self.pid = Some(child.id());
+        // With several monitors wf-recorder would ask which one to use
+        if let CaptureRegion::FullScreen = self.config.region {
+            let outputs = display::list_outputs();
+            if outputs.len() > 1 {
+                if let Some(output) = display::primary_output(&outputs) {
+                    command = command.output(output.name.clone());
+                }
+            }
+        }
+
+        // Queued ahead of wf-recorder's own output so setup warnings come first
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+
+        // Fall back to software encoding when the GPU cannot be used
+        match detect(
+            self.config.encoder_backend,
+            self.config.codec,
+            self.config.hardware_device.as_deref(),
+            Path::new(DEV_DIR),
+        ) {
+            Ok(Some(device)) => command = command.hardware(device),
+            Ok(None) => {}
+            Err(reason) => {
+                let warning = format!("{reason}, using software encoding");
+                let _ = event_tx.send(RecorderEvent::Warning(warning));
+            }
+        }
+
+        self.command = Some(command);
+        self.output_file = Some(output_file);
+        self.spawn_segment((event_tx, event_rx))
+    }
+
+    /// Whether a recording was started and is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.command.is_some() && self.process.is_none()
+    }
+
+    /// Ends the current segment. wf-recorder finalizes it in the background.
+    pub fn pause(&mut self) -> Result<()> {
+        // Joining the segments later needs ffmpeg, better to find out now
+        which::which("ffmpeg").context("ffmpeg not found. It is needed to pause recordings.")?;
+        self.end_segment()
+    }
+
+    fn end_segment(&mut self) -> Result<()> {
+        let process = self.process.take().context("Not recording")?;
+        // Send errors only mean the supervisor has already seen the exit
+        let _ = process.signals.send(libc::SIGINT);
+        self.finishing
+            .retain(|process| process.exit.borrow().is_none());
+        self.finishing.push(process);
+        if let Some(started) = self.segment_started.take() {
+            self.part_recorded += started.elapsed();
+        }
+        Ok(())
+    }
+
+    /// Ends the current segment and starts the next one right away.
+    pub fn rotate(&mut self) -> Result<()> {
+        self.end_segment()?;
+        self.spawn_segment(mpsc::unbounded_channel())
+    }
+
+    /// How long the current segment has been recording.
+    pub fn segment_age(&self) -> Option<Duration> {
+        self.segment_started.map(|started| started.elapsed())
+    }
+
+    /// Deletes buffered segments that fell out of the replay window.
+    pub fn prune_replay(&self) {
+        if let Some(buffer) = &self.replay {
+            buffer.prune(SystemTime::now());
+        }
+    }
+
+    /// Ends the current segment and returns everything buffered up to now,
+    /// to be saved with `ReplaySnapshot::save` while buffering continues.
+    pub fn snapshot_replay(&mut self) -> Result<ReplaySnapshot> {
+        let buffer = self.replay.clone().context("Not buffering a replay")?;
+        which::which("ffmpeg").context("ffmpeg not found. It is needed to save replays.")?;
+
+        self.rotate()?;
+        let current = self.segments.last();
+        let segments: Vec<PathBuf> = buffer
+            .segments()
+            .into_iter()
+            .filter(|segment| Some(segment) != current)
+            .collect();
+        let (dir, segments) = buffer
+            .link_snapshot(&segments)
+            .context("Failed to snapshot the replay buffer")?;
+        let exits = self
+            .finishing
+            .iter()
+            .map(|process| process.exit.clone())
+            .collect();
+
+        Ok(ReplaySnapshot {
+            dir,
+            segments,
+            exits,
+        })
+    }
+
+    /// Where a replay saved now ends up.
+    pub fn replay_filename(&self) -> Result<PathBuf> {
+        let (_, path) = self.generate_filename("replay", None)?;
+        Ok(path)
+    }
+
+    /// File being recorded, the current part's when splitting.
+    pub fn output_file(&self) -> Option<&Path> {
+        self.output_file.as_deref()
+    }
+
+    /// Directory wf-recorder is writing to, once started.
+    pub fn output_dir(&self) -> Option<&Path> {
+        self.output_file.as_deref().and_then(Path::parent)
+    }
+
+    /// Size of everything recorded so far, across all segments and parts.
+    pub fn recorded_bytes(&self) -> u64 {
+        self.parts_bytes + segments_bytes(&self.segments)
+    }
+
+    /// Number of the part being recorded, starting at 1, when splitting.
+    pub fn part(&self) -> Option<usize> {
+        self.part
+    }
+
+    /// Whether the current part has reached the configured length. Never
+    /// while paused: `resume` moves on to the next part itself then.
+    pub fn part_due(&self) -> bool {
+        let recorded = self.part_recorded + self.segment_age().unwrap_or_default();
+        self.process.is_some()
+            && self.part.is_some()
+            && self
+                .config
+                .split_after
+                .is_some_and(|length| recorded >= length)
+    }
+
+    /// Moves the recording on to the next part file, resuming it when
+    /// paused. The current part is joined in the background once its
+    /// wf-recorder has exited.
+    pub fn next_part(&mut self) -> Result<()> {
+        let (Some(part), Some(name)) = (self.part, &self.base_name) else {
+            anyhow::bail!("Not splitting this recording");
+        };
+        if self.process.is_none() && !self.is_paused() {
+            anyhow::bail!("Not recording");
+        }
+        let previous = self.process.take();
+
+        // Only the first part's name was checked when the recording started
+        let output_file = unique_path(self.part_filename(name, Some(part + 1)));
+        let finished_output = self.output_file.replace(output_file);
+        let finished_segments = std::mem::take(&mut self.segments);
+
+        // The next part starts before this one ends so no frames fall
+        // between them; the parts overlap by wf-recorder's shutdown instead
+        if let Err(e) = self.spawn_segment(mpsc::unbounded_channel()) {
+            self.output_file = finished_output;
+            self.segments = finished_segments;
+            self.process = previous;
+            return Err(e);
+        }
+
+        if let Some(previous) = previous {
+            // Send errors only mean the supervisor has already seen the exit
+            let _ = previous.signals.send(libc::SIGINT);
+            self.finishing
+                .retain(|process| process.exit.borrow().is_none());
+            self.finishing.push(previous);
+        }
 
+        let exits = self
+            .finishing
+            .iter()
+            .map(|process| process.exit.clone())
+            .collect();
+        if let Some(output) = finished_output {
+            self.parts_bytes += segments_bytes(&finished_segments);
+            self.parts
+                .push(tokio::spawn(finish_part(exits, finished_segments, output)));
+        }
+        self.part = Some(part + 1);
+        self.part_recorded = Duration::default();
         Ok(())
     }
 
-    pub fn stop(&mut self) -> Result<()> {
-        if let Some(pid) = self.pid.take() {
-            Command::new("kill")
-                .args(["-s", "INT", &pid.to_string()])
-                .spawn()?
-                .wait()?;
+    /// Starts the next segment with the arguments resolved by `start`.
+    pub fn resume(&mut self) -> Result<()> {
+        if !self.is_paused() {
+            anyhow::bail!("Not paused");
         }
+        // A part that filled up before the pause is not added to
+        let part_full = self
+            .config
+            .split_after
+            .is_some_and(|length| self.part_recorded >= length);
+        if self.part.is_some() && part_full {
+            return self.next_part();
+        }
+        self.spawn_segment(mpsc::unbounded_channel())
+    }
+
+    // Runs wf-recorder into the next segment file. Events queued on the
+    // channel beforehand are delivered ahead of wf-recorder's own output.
+    fn spawn_segment(
+        &mut self,
+        (event_tx, event_rx): (
+            mpsc::UnboundedSender<RecorderEvent>,
+            mpsc::UnboundedReceiver<RecorderEvent>,
+        ),
+    ) -> Result<()> {
+        let (Some(command), Some(output_file)) = (&self.command, &self.output_file) else {
+            anyhow::bail!("Recording was not started");
+        };
+        let segment = segments::segment_path(output_file, self.segments.len() + 1);
+        let command = command.clone().output_file(segment.clone());
+
+        let mut cmd = tokio::process::Command::new(&self.config.wf_recorder);
+        cmd.args(command.args());
+
+        // Capture output so progress and failures can be reported to the app
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // Start the recording process
+        let mut child = cmd.spawn().context("Failed to start wf-recorder")?;
+        let pid = child.id().context("wf-recorder exited immediately")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("Failed to capture wf-recorder stdout")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("Failed to capture wf-recorder stderr")?;
+
+        let (signal_tx, signal_rx) = mpsc::unbounded_channel();
+        let (exit_tx, exit_rx) = watch::channel(None);
+        tokio::spawn(supervise(
+            child, stdout, stderr, signal_rx, event_tx, exit_tx,
+        ));
+
+        self.segments.push(segment);
+        self.segment_started = Some(Instant::now());
+        self.process = Some(RecorderProcess {
+            pid,
+            signals: signal_tx,
+            events: Arc::new(Mutex::new(event_rx)),
+            exit: exit_rx,
+        });
+
         Ok(())
     }
+
+    /// Process id of the running wf-recorder, if any.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().map(|process| process.pid)
+    }
+
+    /// Events parsed from wf-recorder's output, ending after `Finished`.
+    ///
+    /// Every stream returned shares the same queue, so an event is delivered
+    /// to only one of them. Empty when no recording was started.
+    pub fn events(&self) -> impl Stream<Item = RecorderEvent> + Send + 'static {
+        let events = self.process.as_ref().map(|process| process.events.clone());
+        stream::unfold(events, |events| async move {
+            let event = events.as_ref()?.lock().await.recv().await?;
+            Some((event, events))
+        })
+    }
+
+    /// Stops wf-recorder, waits for every segment to be written and joins
+    /// them into the output file.
+    ///
+    /// SIGINT lets wf-recorder flush the muxer; if it does not exit in time it
+    /// is escalated to SIGTERM and finally SIGKILL. Returns how the last
+    /// segment's wf-recorder exited, `None` when no recording was started.
+    pub async fn stop(&mut self) -> Result<Option<ExitStatus>> {
+        let mut status = None;
+        // One wf-recorder getting lost is no reason to leave the others running
+        let mut failed = None;
+        let processes = self.finishing.drain(..).chain(self.process.take());
+        for process in processes.collect::<Vec<_>>() {
+            match stop_process(process).await {
+                Ok(exit) => status = Some(exit),
+                Err(e) => {
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+        // Only tear down the mix once nothing records from it anymore
+        self.mix = None;
+        if let Some(e) = failed {
+            return Err(e);
+        }
+
+        for part in self.parts.drain(..).collect::<Vec<_>>() {
+            part.await.context("Failed to save a part")??;
+        }
+
+        // A replay buffer only keeps what was saved explicitly
+        if let Some(buffer) = self.replay.take() {
+            self.output_file = None;
+            let _ = buffer.clear();
+        }
+        if let Some(output_file) = self.output_file.take() {
+            segments::finalize(&self.segments, &output_file).await?;
+        }
+        self.command = None;
+        self.segments.clear();
+
+        Ok(status)
+    }
+}
+
+// Bytes written to the segment files that exist
+fn segments_bytes(segments: &[PathBuf]) -> u64 {
+    segments
+        .iter()
+        .filter_map(|segment| std::fs::metadata(segment).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+// Waits for a part's wf-recorder runs to exit and joins its segments
+async fn finish_part(
+    exits: Vec<watch::Receiver<Option<RecorderExit>>>,
+    segments: Vec<PathBuf>,
+    output: PathBuf,
+) -> Result<()> {
+    for exit in exits {
+        // An unknown exit status still leaves whatever was written
+        let _ = wait_for_exit(exit).await;
+    }
+    segments::finalize(&segments, &output).await
+}
+
+// Stops one wf-recorder and returns how it exited
+async fn stop_process(process: RecorderProcess) -> Result<ExitStatus> {
+    // Send errors only mean the supervisor has already seen the exit
+    let _ = process.signals.send(libc::SIGINT);
+    let exit = wait_for_exit(process.exit.clone());
+    if let Ok(exit) = tokio::time::timeout(INTERRUPT_TIMEOUT, exit).await {
+        return Ok(exit?.status);
+    }
+
+    let _ = process.signals.send(libc::SIGTERM);
+    let exit = wait_for_exit(process.exit.clone());
+    if let Ok(exit) = tokio::time::timeout(TERMINATE_TIMEOUT, exit).await {
+        return Ok(exit?.status);
+    }
+
+    let _ = process.signals.send(libc::SIGKILL);
+    Ok(wait_for_exit(process.exit).await?.status)
+}
+
+async fn wait_for_exit(mut exit: watch::Receiver<Option<RecorderExit>>) -> Result<RecorderExit> {
+    let exit = exit
+        .wait_for(Option::is_some)
+        .await
+        .context("Lost track of wf-recorder")?;
+    Ok(exit.clone().expect("waited for the exit to be set"))
+}
+
+// Owns the wf-recorder child: forwards signals to it, turns its output into
+// events, collects the tail of its stderr and publishes how it exited.
+async fn supervise(
+    mut child: Child,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    mut signals: mpsc::UnboundedReceiver<libc::c_int>,
+    events: mpsc::UnboundedSender<RecorderEvent>,
+    exit: watch::Sender<Option<RecorderExit>>,
+) {
+    let mut stdout = BufReader::new(stdout).lines();
+    let mut stderr = BufReader::new(stderr).lines();
+    let mut stderr_tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+    let (mut stdout_open, mut stderr_open) = (true, true);
+
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status,
+            Some(signal) = signals.recv() => {
+                if let Err(e) = send_signal(&child, signal) {
+                    let _ = events.send(RecorderEvent::Error(format!("{e:#}")));
+                }
+            }
+            line = stdout.next_line(), if stdout_open => match line {
+                Ok(Some(line)) => handle_stdout_line(&events, line),
+                Ok(None) | Err(_) => stdout_open = false,
+            },
+            line = stderr.next_line(), if stderr_open => match line {
+                Ok(Some(line)) => handle_stderr_line(&events, &mut stderr_tail, line),
+                Ok(None) | Err(_) => stderr_open = false,
+            },
+        }
+    };
+
+    // Pick up whatever was written right before the exit
+    while let Ok(Some(line)) = stdout.next_line().await {
+        handle_stdout_line(&events, line);
+    }
+    while let Ok(Some(line)) = stderr.next_line().await {
+        handle_stderr_line(&events, &mut stderr_tail, line);
+    }
+
+    match status {
+        Ok(status) => {
+            let result = RecorderExit {
+                status,
+                stderr_tail: stderr_tail.into(),
+            };
+            let _ = events.send(RecorderEvent::Finished(result.clone()));
+            let _ = exit.send(Some(result));
+        }
+        // Dropping the sender tells waiters the exit status is unknown
+        Err(e) => {
+            let _ = events.send(RecorderEvent::Error(format!(
+                "Failed to wait for wf-recorder: {e}"
+            )));
+        }
+    }
+}
+
+// Output only reaches callers as events, so the headless CLI's stdout stays
+// reserved for what it prints itself
+fn handle_stdout_line(events: &mpsc::UnboundedSender<RecorderEvent>, line: String) {
+    if let Some(event) = RecorderEvent::parse(&line) {
+        let _ = events.send(event);
+    }
+}
+
+fn handle_stderr_line(
+    events: &mpsc::UnboundedSender<RecorderEvent>,
+    tail: &mut VecDeque<String>,
+    line: String,
+) {
+    if let Some(event) = RecorderEvent::parse(&line) {
+        let _ = events.send(event);
+    }
+    if tail.len() == STDERR_TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line);
+}
+
+fn send_signal(child: &Child, signal: libc::c_int) -> Result<()> {
+    // The id is gone once the child has been reaped, nothing left to signal
+    let Some(pid) = child.id() else {
+        return Ok(());
+    };
+
+    // SAFETY: kill(2) has no memory safety requirements; the pid belongs to
+    // our own unreaped child so it cannot have been recycled.
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to signal wf-recorder");
+    }
+    Ok(())
 }
 
 impl Drop for Recorder {
     fn drop(&mut self) {
-        let _ = self.stop();
+        // Ask every still-running wf-recorder to finalize its file on its own.
+        // The signal goes out directly since the runtime may be shutting down
+        // and never get to run the supervisors again.
+        for process in self.finishing.iter().chain(&self.process) {
+            if process.exit.borrow().is_none() {
+                // SAFETY: kill(2) has no memory safety requirements
+                unsafe { libc::kill(process.pid as libc::pid_t, libc::SIGINT) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::{SampleFormat, SampleRate, DEFAULT_FILENAME_TEMPLATE};
+    use futures::StreamExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    // Stands in for wf-recorder: saves its arguments next to the output,
+    // reports the way the real one does and writes the file when
+    // interrupted. An `exit-code` file there makes it fail with that code,
+    // an `exit-after` file makes it fail on its own after that many seconds.
+    const FAKE_WF_RECORDER: &str = r#"#!/bin/sh
+for arg in "$@"; do
+    [ "$previous" = "-f" ] && out="$arg"
+    previous="$arg"
+done
+dir=$(dirname "$out")
+printf '%s\n' "$@" > "$dir/args"
+code=$(cat "$dir/exit-code" 2>/dev/null || echo 0)
+trap 'printf recorded > "$out"; exit "$code"' INT
+echo "selected region 0,0 1920x1080" >&2
+echo "Recording..." >&2
+if [ -f "$dir/exit-after" ]; then
+    sleep "$(cat "$dir/exit-after")"
+    printf partial > "$out"
+    echo "Error: failed to write frame: No space left on device" >&2
+    exit "$code"
+fi
+while :; do sleep 0.05; done
+"#;
+
+    // Settings that record into a fresh directory of their own, with the
+    // fake wf-recorder in it. Tests remove the directory when done.
+    fn config(name: &str) -> RecordingConfig {
+        let output_dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-recorder-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let wf_recorder = output_dir.join("wf-recorder");
+        std::fs::write(&wf_recorder, FAKE_WF_RECORDER).unwrap();
+        std::fs::set_permissions(&wf_recorder, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        RecordingConfig {
+            format: OutputFormat::Mkv,
+            codec: VideoCodec::H264,
+            quality: QualityPreset::Balanced,
+            custom_quality: CustomQuality::default(),
+            encoder_backend: EncoderBackend::Software,
+            hardware_device: None,
+            audio: AudioSource::None,
+            system_audio_device: None,
+            microphone_device: None,
+            audio_settings: AudioSettings {
+                codec: AudioCodec::Aac,
+                sample_rate: SampleRate::default(),
+                sample_format: SampleFormat::Auto,
+                bitrate: None,
+            },
+            // Neither slurp nor the compositor is asked about a named output
+            region: CaptureRegion::Output(String::from("DP-1")),
+            framerate: Framerate::default(),
+            output_dir,
+            split_after: None,
+            filename_template: String::from(DEFAULT_FILENAME_TEMPLATE),
+            wf_recorder,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_until_interrupted() {
+        let config = config("stop");
+        let output_dir = config.output_dir.clone();
+        let mut recorder = Recorder::new(config);
+
+        recorder.start().unwrap();
+        assert!(recorder.pid().is_some());
+        let output = recorder.output_file().unwrap().to_path_buf();
+        assert_eq!(output.parent(), Some(output_dir.as_path()));
+
+        // Nothing is written before the stop, so wait for the start instead
+        let mut events = Box::pin(recorder.events());
+        let mut selected = None;
+        loop {
+            match events.next().await.unwrap() {
+                RecorderEvent::OutputSelected(region) => selected = Some(region),
+                RecorderEvent::Started => break,
+                event => panic!("unexpected {event:?}"),
+            }
+        }
+        assert_eq!(selected.as_deref(), Some("0,0 1920x1080"));
+        let args = std::fs::read_to_string(output_dir.join("args")).unwrap();
+        assert!(args.contains("\n-o\nDP-1\n"), "{args}");
+
+        let status = recorder.stop().await.unwrap().unwrap();
+        assert!(status.success());
+        // The single segment became the output
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "recorded");
+        assert!(!segments::segment_path(&output, 1).exists());
+
+        match events.next().await.unwrap() {
+            RecorderEvent::Finished(exit) => assert_eq!(exit.status.code(), Some(0)),
+            event => panic!("unexpected {event:?}"),
+        }
+        assert!(events.next().await.is_none());
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn exit_status_is_reported() {
+        let config = config("status");
+        let output_dir = config.output_dir.clone();
+        std::fs::write(output_dir.join("exit-code"), "3").unwrap();
+        let mut recorder = Recorder::new(config);
+
+        recorder.start().unwrap();
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+
+        let status = recorder.stop().await.unwrap().unwrap();
+        assert_eq!(status.code(), Some(3));
+        match events.next().await.unwrap() {
+            RecorderEvent::Finished(exit) => assert_eq!(exit.status.code(), Some(3)),
+            event => panic!("unexpected {event:?}"),
+        }
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn exits_on_its_own() {
+        let config = config("crash");
+        let output_dir = config.output_dir.clone();
+        std::fs::write(output_dir.join("exit-code"), "1").unwrap();
+        std::fs::write(output_dir.join("exit-after"), "1").unwrap();
+        let mut recorder = Recorder::new(config);
+
+        recorder.start().unwrap();
+        let output = recorder.output_file().unwrap().to_path_buf();
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+
+        // Nothing is stopped, wf-recorder ends by itself
+        let started = std::time::Instant::now();
+        let mut error = None;
+        let exit = loop {
+            match events.next().await.unwrap() {
+                RecorderEvent::Error(message) => error = Some(message),
+                RecorderEvent::Finished(exit) => break exit,
+                event => panic!("unexpected {event:?}"),
+            }
+        };
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert_eq!(exit.status.code(), Some(1));
+        assert_eq!(
+            exit.stderr_tail.last().map(String::as_str),
+            Some("Error: failed to write frame: No space left on device")
+        );
+        assert_eq!(
+            error.as_deref(),
+            exit.stderr_tail.last().map(String::as_str)
+        );
+        assert!(events.next().await.is_none());
+
+        // What was written before the failure is still saved
+        let status = recorder.stop().await.unwrap().unwrap();
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "partial");
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_part_waits_for_resume() {
+        let config = RecordingConfig {
+            split_after: Some(Duration::from_millis(200)),
+            ..config("split")
+        };
+        let output_dir = config.output_dir.clone();
+        let mut recorder = Recorder::new(config);
+
+        recorder.start().unwrap();
+        assert_eq!(recorder.part(), Some(1));
+        let first = recorder.output_file().unwrap().to_path_buf();
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(recorder.part_due());
+
+        // Paused after the part filled up: nothing to roll over until resumed
+        recorder.end_segment().unwrap();
+        assert!(recorder.is_paused());
+        assert!(!recorder.part_due());
+
+        recorder.resume().unwrap();
+        assert!(!recorder.is_paused());
+        assert_eq!(recorder.part(), Some(2));
+        let second = recorder.output_file().unwrap().to_path_buf();
+        assert_ne!(first, second);
+        assert!(!recorder.part_due());
+
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+        recorder.stop().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "recorded");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "recorded");
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dropping_interrupts_every_wf_recorder() {
+        let config = config("drop");
+        let output_dir = config.output_dir.clone();
+        let mut recorder = Recorder::new(config);
+
+        recorder.start().unwrap();
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+        // A segment still finishing that has not been signalled yet
+        let finishing = recorder.process.take().unwrap();
+        recorder.finishing.push(finishing);
+        recorder.resume().unwrap();
+        let mut events = Box::pin(recorder.events());
+        while !matches!(events.next().await, Some(RecorderEvent::Started)) {}
+
+        let segments = recorder.segments.clone();
+        assert_eq!(segments.len(), 2);
+        drop(recorder);
+        for segment in &segments {
+            let mut written = false;
+            for _ in 0..100 {
+                written = std::fs::read_to_string(segment).is_ok_and(|s| s == "recorded");
+                if written {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert!(written, "{} was not written", segment.display());
+        }
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stop_without_start() {
+        let mut recorder = Recorder::new(config("idle"));
+        assert!(recorder.stop().await.unwrap().is_none());
+        std::fs::remove_dir_all(&recorder.config.output_dir).unwrap();
     }
 }
@@ -1,3 +1,25 @@
+mod audio_codec;
+mod command;
+mod events;
+mod filename;
+mod hardware;
 mod implementation;
+mod quality;
+mod replay;
+mod segments;
+mod storage;
 
-pub use implementation::{AudioSource, CaptureRegion, OutputFormat, Recorder, RecordingConfig};
+pub use audio_codec::{AudioCodec, AudioSettings, SampleFormat, SampleRate};
+pub use command::WfRecorderCommand;
+pub use events::RecorderEvent;
+pub use filename::{
+    part_suffix, unique_path, FilenameContext, FilenameTemplate, DEFAULT_FILENAME_TEMPLATE,
+};
+pub use hardware::{detect, render_nodes, EncoderBackend, HardwareDevice, DEV_DIR};
+pub use implementation::{
+    AudioSource, CaptureRegion, Framerate, OutputFormat, Recorder, RecorderExit, RecordingConfig,
+    VideoCodec,
+};
+pub use quality::{encoder_params, is_valid_bitrate, CustomQuality, QualityPreset};
+pub use replay::{ReplayBuffer, ReplaySnapshot, REPLAY_SEGMENT_LENGTH};
+pub use storage::{free_space, preflight, DiskSpace, DiskWatchdog, Statvfs};
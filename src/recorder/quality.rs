@@ -0,0 +1,627 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+use super::{EncoderBackend, VideoCodec};
+
+/// Named trade-offs between file size, quality and CPU load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Draft,
+    Balanced,
+    High,
+    Lossless,
+    Custom,
+}
+
+impl QualityPreset {
+    pub fn all() -> &'static [QualityPreset] {
+        &[
+            QualityPreset::Draft,
+            QualityPreset::Balanced,
+            QualityPreset::High,
+            QualityPreset::Lossless,
+            QualityPreset::Custom,
+        ]
+    }
+}
+
+/// User supplied encoder settings for `QualityPreset::Custom`.
+///
+/// Unset fields leave the encoder default in place.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomQuality {
+    pub crf: Option<u32>,
+    /// Speed preset, passed as `preset` or `cpu-used` depending on the encoder
+    pub preset: Option<String>,
+    /// Target bitrate in ffmpeg notation, e.g. `2500k` or `4M`
+    pub bitrate: Option<String>,
+}
+
+/// Accepts plain bits per second or a `k`/`M` suffixed amount.
+pub fn is_valid_bitrate(bitrate: &str) -> bool {
+    let digits = bitrate
+        .strip_suffix(['k', 'K', 'm', 'M'])
+        .unwrap_or(bitrate);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Encoder parameters for `codec`, passed to wf-recorder as `-p key=value`.
+pub fn encoder_params(
+    preset: QualityPreset,
+    custom: &CustomQuality,
+    codec: VideoCodec,
+    backend: EncoderBackend,
+) -> Vec<(&'static str, String)> {
+    match backend {
+        EncoderBackend::Software => software_params(preset, custom, codec),
+        EncoderBackend::Vaapi => vaapi_params(preset, custom, codec),
+        EncoderBackend::Nvenc => nvenc_params(preset, custom),
+    }
+}
+
+fn software_params(
+    preset: QualityPreset,
+    custom: &CustomQuality,
+    codec: VideoCodec,
+) -> Vec<(&'static str, String)> {
+    if preset == QualityPreset::Custom {
+        return custom_params(custom, codec);
+    }
+
+    let param = |key: &'static str, value: &str| (key, value.to_string());
+
+    match (codec, preset) {
+        // x264/x265: CRF 0-51, presets trade CPU for size at equal quality
+        (VideoCodec::H264, QualityPreset::Draft) => {
+            vec![param("crf", "32"), param("preset", "ultrafast")]
+        }
+        (VideoCodec::H264, QualityPreset::Balanced) => {
+            vec![param("crf", "26"), param("preset", "veryfast")]
+        }
+        (VideoCodec::H264, QualityPreset::High) => {
+            vec![param("crf", "20"), param("preset", "faster")]
+        }
+        (VideoCodec::H264, _) => vec![param("crf", "0"), param("preset", "ultrafast")],
+        (VideoCodec::H265, QualityPreset::Draft) => {
+            vec![param("crf", "34"), param("preset", "ultrafast")]
+        }
+        (VideoCodec::H265, QualityPreset::Balanced) => {
+            vec![param("crf", "28"), param("preset", "veryfast")]
+        }
+        (VideoCodec::H265, QualityPreset::High) => {
+            vec![param("crf", "22"), param("preset", "faster")]
+        }
+        (VideoCodec::H265, _) => vec![
+            param("x265-params", "lossless=1"),
+            param("preset", "ultrafast"),
+        ],
+
+        // VP8 has no lossless mode and needs a bitrate cap for CRF to apply
+        (VideoCodec::Vp8, QualityPreset::Draft) => vec![
+            param("crf", "40"),
+            param("b", "1M"),
+            param("deadline", "realtime"),
+            param("cpu-used", "8"),
+        ],
+        (VideoCodec::Vp8, QualityPreset::Balanced) => vec![
+            param("crf", "24"),
+            param("b", "4M"),
+            param("deadline", "realtime"),
+            param("cpu-used", "6"),
+        ],
+        (VideoCodec::Vp8, _) => vec![
+            param("crf", "10"),
+            param("b", "12M"),
+            param("deadline", "realtime"),
+            param("cpu-used", "4"),
+        ],
+
+        // VP9: a zero bitrate switches to constant quality mode
+        (VideoCodec::Vp9, QualityPreset::Draft) => vec![
+            param("crf", "45"),
+            param("b", "0"),
+            param("deadline", "realtime"),
+            param("cpu-used", "8"),
+        ],
+        (VideoCodec::Vp9, QualityPreset::Balanced) => vec![
+            param("crf", "35"),
+            param("b", "0"),
+            param("deadline", "realtime"),
+            param("cpu-used", "7"),
+        ],
+        (VideoCodec::Vp9, QualityPreset::High) => vec![
+            param("crf", "24"),
+            param("b", "0"),
+            param("deadline", "realtime"),
+            param("cpu-used", "5"),
+        ],
+        (VideoCodec::Vp9, _) => vec![
+            param("lossless", "1"),
+            param("deadline", "realtime"),
+            param("cpu-used", "8"),
+        ],
+
+        // SVT-AV1 has no lossless mode, so Lossless maps to its best preset
+        (VideoCodec::Av1Svt, QualityPreset::Draft) => {
+            vec![param("crf", "45"), param("preset", "12")]
+        }
+        (VideoCodec::Av1Svt, QualityPreset::Balanced) => {
+            vec![param("crf", "35"), param("preset", "10")]
+        }
+        (VideoCodec::Av1Svt, _) => vec![param("crf", "26"), param("preset", "8")],
+
+        // libaom is slow, so every preset runs in realtime mode
+        (VideoCodec::Av1Aom, QualityPreset::Draft) => vec![
+            param("crf", "45"),
+            param("usage", "realtime"),
+            param("cpu-used", "10"),
+        ],
+        (VideoCodec::Av1Aom, QualityPreset::Balanced) => vec![
+            param("crf", "35"),
+            param("usage", "realtime"),
+            param("cpu-used", "9"),
+        ],
+        (VideoCodec::Av1Aom, QualityPreset::High) => vec![
+            param("crf", "26"),
+            param("usage", "realtime"),
+            param("cpu-used", "8"),
+        ],
+        (VideoCodec::Av1Aom, _) => vec![
+            param("crf", "0"),
+            param("usage", "realtime"),
+            param("cpu-used", "8"),
+        ],
+
+        // FFV1 is always lossless
+        (VideoCodec::Ffv1, _) => Vec::new(),
+    }
+}
+
+// CRF values the software encoder accepts; libvpx refuses VP8 below 4 and
+// SVT-AV1 starts at 1
+fn crf_range(codec: VideoCodec) -> RangeInclusive<u32> {
+    match codec {
+        VideoCodec::H264 | VideoCodec::H265 => 0..=51,
+        VideoCodec::Vp8 => 4..=63,
+        VideoCodec::Vp9 | VideoCodec::Av1Aom => 0..=63,
+        VideoCodec::Av1Svt => 1..=63,
+        VideoCodec::Ffv1 => 0..=0,
+    }
+}
+
+fn custom_params(custom: &CustomQuality, codec: VideoCodec) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+
+    if codec == VideoCodec::Ffv1 {
+        return params;
+    }
+
+    // Out of range, the encoder would refuse to start
+    if let Some(crf) = custom.crf {
+        let range = crf_range(codec);
+        params.push(("crf", crf.clamp(*range.start(), *range.end()).to_string()));
+    }
+
+    if let Some(preset) = custom.preset.as_deref().filter(|p| !p.is_empty()) {
+        let key = match codec {
+            VideoCodec::Vp8 | VideoCodec::Vp9 | VideoCodec::Av1Aom => "cpu-used",
+            _ => "preset",
+        };
+        params.push((key, preset.to_string()));
+    }
+
+    if let Some(bitrate) = custom.bitrate.as_deref().filter(|b| is_valid_bitrate(b)) {
+        params.push(("b", bitrate.to_string()));
+    }
+
+    params
+}
+
+// VAAPI uses constant QP; 0 means "unset" there and there is no lossless mode.
+// Only h264_vaapi and hevc_vaapi have a `qp` option, the VP8, VP9 and AV1
+// encoders take a quantizer index through `global_quality` instead.
+fn vaapi_params(
+    preset: QualityPreset,
+    custom: &CustomQuality,
+    codec: VideoCodec,
+) -> Vec<(&'static str, String)> {
+    let mut params = Vec::new();
+    let qp = match preset {
+        QualityPreset::Draft => Some(32),
+        QualityPreset::Balanced => Some(26),
+        QualityPreset::High => Some(20),
+        QualityPreset::Lossless => Some(1),
+        QualityPreset::Custom => custom.crf,
+    };
+
+    // Presets are on the H.264 scale of 0-51, which the q index ranges stretch
+    let quality = qp.and_then(|qp| match codec {
+        VideoCodec::H264 | VideoCodec::H265 => Some(("qp", qp.min(51))),
+        VideoCodec::Vp8 | VideoCodec::Vp9 => Some(("global_quality", scale_qp(qp, 127))),
+        VideoCodec::Av1Svt | VideoCodec::Av1Aom => Some(("global_quality", scale_qp(qp, 255))),
+        _ => None,
+    });
+    if let Some((key, value)) = quality {
+        params.push(("rc_mode", String::from("CQP")));
+        params.push((key, value.to_string()));
+    }
+
+    if preset == QualityPreset::Custom {
+        if let Some(bitrate) = custom.bitrate.as_deref().filter(|b| is_valid_bitrate(b)) {
+            params.push(("b", bitrate.to_string()));
+        }
+    }
+
+    params
+}
+
+// Maps a QP of 0-51 onto a quantizer index of 0-`max`, keeping it above 0
+fn scale_qp(qp: u32, max: u32) -> u32 {
+    (qp.min(51) * max / 51).max(1)
+}
+
+// NVENC presets run from p1 (fastest) to p7 (best quality)
+fn nvenc_params(preset: QualityPreset, custom: &CustomQuality) -> Vec<(&'static str, String)> {
+    let param = |key: &'static str, value: &str| (key, value.to_string());
+
+    match preset {
+        QualityPreset::Draft => vec![param("rc", "vbr"), param("cq", "32"), param("preset", "p1")],
+        QualityPreset::Balanced => {
+            vec![param("rc", "vbr"), param("cq", "26"), param("preset", "p4")]
+        }
+        QualityPreset::High => vec![param("rc", "vbr"), param("cq", "20"), param("preset", "p6")],
+        QualityPreset::Lossless => vec![param("tune", "lossless"), param("preset", "p4")],
+        QualityPreset::Custom => {
+            let mut params = Vec::new();
+            // cq runs from 1 to 51, 0 would leave the quality to NVENC
+            if let Some(crf) = custom.crf {
+                params.push(param("rc", "vbr"));
+                params.push(("cq", crf.clamp(1, 51).to_string()));
+            }
+            if let Some(preset) = custom.preset.as_deref().filter(|p| !p.is_empty()) {
+                params.push(param("preset", preset));
+            }
+            if let Some(bitrate) = custom.bitrate.as_deref().filter(|b| is_valid_bitrate(b)) {
+                params.push(param("b", bitrate));
+            }
+            params
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vaapi(preset: QualityPreset, codec: VideoCodec) -> Vec<(&'static str, String)> {
+        encoder_params(
+            preset,
+            &CustomQuality::default(),
+            codec,
+            EncoderBackend::Vaapi,
+        )
+    }
+
+    fn params(pairs: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn vaapi_qp_only_for_h264_and_hevc() {
+        for codec in [VideoCodec::H264, VideoCodec::H265] {
+            assert_eq!(
+                vaapi(QualityPreset::Balanced, codec),
+                params(&[("rc_mode", "CQP"), ("qp", "26")])
+            );
+        }
+        for codec in [VideoCodec::Vp8, VideoCodec::Vp9] {
+            assert_eq!(
+                vaapi(QualityPreset::Balanced, codec),
+                params(&[("rc_mode", "CQP"), ("global_quality", "64")])
+            );
+        }
+        assert_eq!(
+            vaapi(QualityPreset::Balanced, VideoCodec::Av1Svt),
+            params(&[("rc_mode", "CQP"), ("global_quality", "130")])
+        );
+        assert_eq!(
+            vaapi(QualityPreset::Lossless, VideoCodec::Vp9),
+            params(&[("rc_mode", "CQP"), ("global_quality", "2")])
+        );
+    }
+
+    #[test]
+    fn vaapi_custom() {
+        let custom = CustomQuality {
+            crf: Some(60),
+            preset: Some(String::from("slow")),
+            bitrate: Some(String::from("6M")),
+        };
+        assert_eq!(
+            encoder_params(
+                QualityPreset::Custom,
+                &custom,
+                VideoCodec::H264,
+                EncoderBackend::Vaapi
+            ),
+            params(&[("rc_mode", "CQP"), ("qp", "51"), ("b", "6M")])
+        );
+
+        // Without a quality the encoder keeps its own rate control
+        let custom = CustomQuality {
+            crf: None,
+            ..custom
+        };
+        assert_eq!(
+            encoder_params(
+                QualityPreset::Custom,
+                &custom,
+                VideoCodec::Vp8,
+                EncoderBackend::Vaapi
+            ),
+            params(&[("b", "6M")])
+        );
+    }
+
+    fn software(preset: QualityPreset, codec: VideoCodec) -> Vec<(&'static str, String)> {
+        encoder_params(
+            preset,
+            &CustomQuality::default(),
+            codec,
+            EncoderBackend::Software,
+        )
+    }
+
+    fn custom(crf: u32, codec: VideoCodec, backend: EncoderBackend) -> Vec<(&'static str, String)> {
+        let custom = CustomQuality {
+            crf: Some(crf),
+            ..CustomQuality::default()
+        };
+        encoder_params(QualityPreset::Custom, &custom, codec, backend)
+    }
+
+    #[test]
+    fn software_presets() {
+        use QualityPreset::*;
+        type Pairs = &'static [(&'static str, &'static str)];
+        let cases: &[(VideoCodec, QualityPreset, Pairs)] = &[
+            (
+                VideoCodec::H264,
+                Draft,
+                &[("crf", "32"), ("preset", "ultrafast")],
+            ),
+            (
+                VideoCodec::H264,
+                Balanced,
+                &[("crf", "26"), ("preset", "veryfast")],
+            ),
+            (
+                VideoCodec::H264,
+                High,
+                &[("crf", "20"), ("preset", "faster")],
+            ),
+            (
+                VideoCodec::H264,
+                Lossless,
+                &[("crf", "0"), ("preset", "ultrafast")],
+            ),
+            (
+                VideoCodec::Vp8,
+                Draft,
+                &[
+                    ("crf", "40"),
+                    ("b", "1M"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "8"),
+                ],
+            ),
+            (
+                VideoCodec::Vp8,
+                Balanced,
+                &[
+                    ("crf", "24"),
+                    ("b", "4M"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "6"),
+                ],
+            ),
+            (
+                VideoCodec::Vp8,
+                High,
+                &[
+                    ("crf", "10"),
+                    ("b", "12M"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "4"),
+                ],
+            ),
+            // VP8 has no lossless mode
+            (
+                VideoCodec::Vp8,
+                Lossless,
+                &[
+                    ("crf", "10"),
+                    ("b", "12M"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "4"),
+                ],
+            ),
+            (
+                VideoCodec::Vp9,
+                Draft,
+                &[
+                    ("crf", "45"),
+                    ("b", "0"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "8"),
+                ],
+            ),
+            (
+                VideoCodec::Vp9,
+                Balanced,
+                &[
+                    ("crf", "35"),
+                    ("b", "0"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "7"),
+                ],
+            ),
+            (
+                VideoCodec::Vp9,
+                High,
+                &[
+                    ("crf", "24"),
+                    ("b", "0"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "5"),
+                ],
+            ),
+            (
+                VideoCodec::Vp9,
+                Lossless,
+                &[
+                    ("lossless", "1"),
+                    ("deadline", "realtime"),
+                    ("cpu-used", "8"),
+                ],
+            ),
+            (
+                VideoCodec::Av1Svt,
+                Draft,
+                &[("crf", "45"), ("preset", "12")],
+            ),
+            (
+                VideoCodec::Av1Svt,
+                Balanced,
+                &[("crf", "35"), ("preset", "10")],
+            ),
+            (VideoCodec::Av1Svt, High, &[("crf", "26"), ("preset", "8")]),
+            (
+                VideoCodec::Av1Svt,
+                Lossless,
+                &[("crf", "26"), ("preset", "8")],
+            ),
+            (
+                VideoCodec::Av1Aom,
+                Draft,
+                &[("crf", "45"), ("usage", "realtime"), ("cpu-used", "10")],
+            ),
+            (
+                VideoCodec::Av1Aom,
+                Lossless,
+                &[("crf", "0"), ("usage", "realtime"), ("cpu-used", "8")],
+            ),
+            (VideoCodec::Ffv1, Lossless, &[]),
+        ];
+        for (codec, preset, expected) in cases {
+            assert_eq!(
+                software(*preset, *codec),
+                params(expected),
+                "{codec:?} {preset:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nvenc_presets() {
+        let nvenc = |preset| {
+            encoder_params(
+                preset,
+                &CustomQuality::default(),
+                VideoCodec::H264,
+                EncoderBackend::Nvenc,
+            )
+        };
+        assert_eq!(
+            nvenc(QualityPreset::Draft),
+            params(&[("rc", "vbr"), ("cq", "32"), ("preset", "p1")])
+        );
+        assert_eq!(
+            nvenc(QualityPreset::Balanced),
+            params(&[("rc", "vbr"), ("cq", "26"), ("preset", "p4")])
+        );
+        assert_eq!(
+            nvenc(QualityPreset::High),
+            params(&[("rc", "vbr"), ("cq", "20"), ("preset", "p6")])
+        );
+        assert_eq!(
+            nvenc(QualityPreset::Lossless),
+            params(&[("tune", "lossless"), ("preset", "p4")])
+        );
+        // Nothing set, nothing passed
+        assert!(nvenc(QualityPreset::Custom).is_empty());
+    }
+
+    #[test]
+    fn custom_software_params() {
+        let custom = CustomQuality {
+            crf: Some(23),
+            preset: Some(String::from("medium")),
+            bitrate: Some(String::from("2500k")),
+        };
+        let software = |codec| {
+            encoder_params(
+                QualityPreset::Custom,
+                &custom,
+                codec,
+                EncoderBackend::Software,
+            )
+        };
+        assert_eq!(
+            software(VideoCodec::H264),
+            params(&[("crf", "23"), ("preset", "medium"), ("b", "2500k")])
+        );
+        assert_eq!(
+            software(VideoCodec::Vp9),
+            params(&[("crf", "23"), ("cpu-used", "medium"), ("b", "2500k")])
+        );
+        assert!(software(VideoCodec::Ffv1).is_empty());
+
+        // An empty preset and a malformed bitrate are left out
+        let custom = CustomQuality {
+            crf: None,
+            preset: Some(String::new()),
+            bitrate: Some(String::from("fast")),
+        };
+        assert!(encoder_params(
+            QualityPreset::Custom,
+            &custom,
+            VideoCodec::H264,
+            EncoderBackend::Software
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn custom_crf_is_clamped_to_the_encoder() {
+        let cases = [
+            (VideoCodec::H264, 99, "51"),
+            (VideoCodec::H265, 60, "51"),
+            (VideoCodec::Vp8, 0, "4"),
+            (VideoCodec::Vp8, 70, "63"),
+            (VideoCodec::Vp9, 99, "63"),
+            (VideoCodec::Av1Svt, 0, "1"),
+            (VideoCodec::Av1Aom, 63, "63"),
+            (VideoCodec::H264, 0, "0"),
+        ];
+        for (codec, crf, expected) in cases {
+            assert_eq!(
+                custom(crf, codec, EncoderBackend::Software),
+                params(&[("crf", expected)]),
+                "{codec:?} {crf}"
+            );
+        }
+
+        assert_eq!(
+            custom(80, VideoCodec::H265, EncoderBackend::Nvenc),
+            params(&[("rc", "vbr"), ("cq", "51")])
+        );
+        assert_eq!(
+            custom(0, VideoCodec::H264, EncoderBackend::Nvenc),
+            params(&[("rc", "vbr"), ("cq", "1")])
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+use super::{segments, RecorderExit};
+
+/// How long each buffered segment runs before wf-recorder is restarted.
+/// Retention happens in steps of this size.
+pub const REPLAY_SEGMENT_LENGTH: Duration = Duration::from_secs(30);
+
+// How long a saved replay waits for the segment that was just ended
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Segments are named after `replay.<ext>`, e.g. `replay.seg002.mkv`
+const FILE_STEM: &str = "replay";
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+
+/// The cache directory a replay buffer keeps its segments in, and how far
+/// back they are kept.
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    dir: PathBuf,
+    window: Duration,
+}
+
+impl ReplayBuffer {
+    pub fn new(dir: PathBuf, window: Duration) -> Self {
+        Self { dir, window }
+    }
+
+    /// `$XDG_CACHE_HOME/wf-recorder-gui/replay`, or the temp dir without one.
+    pub fn default_dir() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("wf-recorder-gui")
+            .join("replay")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File wf-recorder is pointed at; the segments are written next to it.
+    pub fn output_file(&self, extension: &str) -> PathBuf {
+        self.dir.join(format!("{FILE_STEM}.{extension}"))
+    }
+
+    /// Starts over without buffered segments, dropping those an earlier
+    /// session left behind along with its unsaved snapshots. Other files in
+    /// the directory are left alone.
+    pub fn reset(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        self.clear()?;
+
+        for entry in fs::read_dir(&self.dir)?.filter_map(Result::ok) {
+            let is_snapshot = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(SNAPSHOT_PREFIX))
+                .is_some_and(|stamp| stamp.chars().all(|c| c.is_ascii_digit()));
+            if !is_snapshot || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            for link in fs::read_dir(entry.path())?.filter_map(Result::ok) {
+                if is_segment(&link.path()) {
+                    fs::remove_file(link.path())?;
+                }
+            }
+            // Kept if something else was put in there
+            let _ = fs::remove_dir(entry.path());
+        }
+        Ok(())
+    }
+
+    /// Removes the buffered segments. Snapshots still being saved keep
+    /// their own links to them.
+    pub fn clear(&self) -> io::Result<()> {
+        for segment in self.segments() {
+            fs::remove_file(segment)?;
+        }
+        Ok(())
+    }
+
+    /// Hard links `segments` into a directory of their own, so pruning and
+    /// clearing the buffer cannot pull them away while they are saved.
+    pub fn link_snapshot(&self, segments: &[PathBuf]) -> io::Result<(PathBuf, Vec<PathBuf>)> {
+        let stamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir = self.dir.join(format!("{SNAPSHOT_PREFIX}{stamp}"));
+        fs::create_dir(&dir)?;
+
+        let mut links = Vec::new();
+        for segment in segments {
+            let Some(name) = segment.file_name() else {
+                continue;
+            };
+            let link = dir.join(name);
+            fs::hard_link(segment, &link)?;
+            links.push(link);
+        }
+        Ok((dir, links))
+    }
+
+    /// Buffered segments, oldest first.
+    pub fn segments(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut segments: Vec<(usize, PathBuf)> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| is_segment(path))
+            .filter_map(|path| Some((segments::segment_index(&path)?, path)))
+            .collect();
+        segments.sort();
+        segments.into_iter().map(|(_, path)| path).collect()
+    }
+
+    /// Deletes segments last written before the window that ends at `now`
+    /// and returns them.
+    pub fn prune(&self, now: SystemTime) -> Vec<PathBuf> {
+        let Some(cutoff) = now.checked_sub(self.window) else {
+            return Vec::new();
+        };
+
+        let mut removed = Vec::new();
+        for segment in self.segments() {
+            let expired = fs::metadata(&segment)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified < cutoff);
+            if expired && fs::remove_file(&segment).is_ok() {
+                removed.push(segment);
+            }
+        }
+        removed
+    }
+}
+
+// Whether `path` is one of the segments written into a replay buffer
+fn is_segment(path: &Path) -> bool {
+    segments::segment_index(path).is_some()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&format!("{FILE_STEM}.seg")))
+}
+
+/// Segments to save as a replay, taken while the buffer keeps recording.
+pub struct ReplaySnapshot {
+    // Directory holding the links to the segments, removed once saved
+    pub(super) dir: PathBuf,
+    pub(super) segments: Vec<PathBuf>,
+    // wf-recorder runs that may still be writing one of the segments
+    pub(super) exits: Vec<watch::Receiver<Option<RecorderExit>>>,
+}
+
+impl ReplaySnapshot {
+    /// Waits for the segments to be finalized and joins them into `output`.
+    pub async fn save(self, output: PathBuf) -> Result<PathBuf> {
+        for mut exit in self.exits {
+            let finished = exit.wait_for(Option::is_some);
+            // A run that does not stop in time leaves an unfinished segment,
+            // which ffmpeg reports below
+            let _ = tokio::time::timeout(FINALIZE_TIMEOUT, finished).await;
+        }
+
+        let result = if self.segments.is_empty() {
+            Err(anyhow::anyhow!("Nothing has been buffered yet"))
+        } else {
+            segments::concat(&self.segments, &output)
+                .await
+                .context("Failed to save the replay")
+        };
+        let _ = tokio::fs::remove_dir_all(&self.dir).await;
+
+        result.map(|_| output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    // Some time in the past every segment is dated from
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + seconds)
+    }
+
+    fn buffer(name: &str, window: Duration) -> ReplayBuffer {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-replay-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        ReplayBuffer::new(dir, window)
+    }
+
+    // Writes `name` into the buffer, last modified at `modified`
+    fn write(buffer: &ReplayBuffer, name: &str, modified: SystemTime) -> PathBuf {
+        let path = buffer.dir().join(name);
+        File::create(&path).unwrap().set_modified(modified).unwrap();
+        path
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn segments_are_ordered_by_index() {
+        let buffer = buffer("order", Duration::from_secs(60));
+        write(&buffer, "replay.seg010.mkv", at(300));
+        write(&buffer, "replay.seg002.mkv", at(60));
+        write(&buffer, "replay.seg009.mkv", at(270));
+        // Not segments of the buffer
+        write(&buffer, "replay.mkv", at(0));
+        write(&buffer, "notes.seg001.txt", at(0));
+        write(&buffer, "replay.segment.mkv", at(0));
+
+        assert_eq!(
+            names(&buffer.segments()),
+            [
+                "replay.seg002.mkv",
+                "replay.seg009.mkv",
+                "replay.seg010.mkv"
+            ]
+        );
+        fs::remove_dir_all(buffer.dir()).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_the_window() {
+        let buffer = buffer("prune", Duration::from_secs(60));
+        // Segments of 30 seconds, the last one still being written
+        for index in 1..=5 {
+            write(
+                &buffer,
+                &format!("replay.seg{index:03}.mkv"),
+                at(index * 30),
+            );
+        }
+
+        // At 150 the window starts at 90: segment 3 ended right on the
+        // boundary and holds the start of it
+        assert_eq!(
+            names(&buffer.prune(at(150))),
+            ["replay.seg001.mkv", "replay.seg002.mkv"]
+        );
+        assert_eq!(
+            names(&buffer.segments()),
+            [
+                "replay.seg003.mkv",
+                "replay.seg004.mkv",
+                "replay.seg005.mkv"
+            ]
+        );
+
+        // A second later it is out of the window
+        assert_eq!(names(&buffer.prune(at(151))), ["replay.seg003.mkv"]);
+        assert_eq!(buffer.prune(at(151)), Vec::<PathBuf>::new());
+        fs::remove_dir_all(buffer.dir()).unwrap();
+    }
+
+    #[test]
+    fn prune_before_a_full_window() {
+        let buffer = buffer("early", Duration::from_secs(u64::MAX / 4));
+        write(&buffer, "replay.seg001.mkv", at(0));
+        assert_eq!(buffer.prune(at(30)), Vec::<PathBuf>::new());
+        fs::remove_dir_all(buffer.dir()).unwrap();
+    }
+
+    #[test]
+    fn reset_removes_only_its_own_files() {
+        let buffer = buffer("reset", Duration::from_secs(60));
+        let segment = write(&buffer, "replay.seg001.webm", at(0));
+        write(&buffer, "replay.seg002.mkv", at(30));
+        let (snapshot, links) = buffer.link_snapshot(&[segment]).unwrap();
+        let (kept_snapshot, _) = buffer.link_snapshot(&[]).unwrap();
+        write(&buffer, "notes.txt", at(0));
+        fs::create_dir(buffer.dir().join("other")).unwrap();
+        write(&buffer, "other/replay.seg001.mkv", at(0));
+        fs::write(kept_snapshot.join("notes.txt"), "").unwrap();
+
+        buffer.reset().unwrap();
+        assert_eq!(buffer.segments(), Vec::<PathBuf>::new());
+        assert!(!links[0].exists());
+        assert!(!snapshot.exists());
+        assert!(buffer.dir().join("notes.txt").exists());
+        assert!(buffer.dir().join("other/replay.seg001.mkv").exists());
+        assert!(kept_snapshot.join("notes.txt").exists());
+        fs::remove_dir_all(buffer.dir()).unwrap();
+
+        // A buffer that never ran starts with its directory
+        buffer.reset().unwrap();
+        assert!(buffer.dir().is_dir());
+        fs::remove_dir_all(buffer.dir()).unwrap();
+    }
+}
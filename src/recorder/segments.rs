@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+
+/// Path of segment `index` of a recording that ends up at `output`, e.g.
+/// `recording_x.seg002.mkv` for `recording_x.mkv`. Segments start at 1.
+pub fn segment_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match output.extension() {
+        Some(extension) => format!("{stem}.seg{index:03}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.seg{index:03}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Index of a file named by `segment_path`.
+pub fn segment_index(path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    let (_, rest) = name.rsplit_once(".seg")?;
+    let digits = rest.split('.').next()?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Input list for ffmpeg's concat demuxer.
+pub fn concat_list(segments: &[PathBuf]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            // Single quotes are closed, escaped and reopened
+            let path = segment.to_string_lossy().replace('\'', r"'\''");
+            format!("file '{path}'\n")
+        })
+        .collect()
+}
+
+/// Joins `segments` into `output` without re-encoding. Fails rather than
+/// overwrite an existing `output`.
+pub async fn concat(segments: &[PathBuf], output: &Path) -> Result<()> {
+    let list = output.with_extension("concat.txt");
+    fs::write(&list, concat_list(segments))
+        .await
+        .context("Failed to write the segment list")?;
+
+    let result = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-n"])
+        .args(["-f", "concat", "-safe", "0", "-i"])
+        .arg(&list)
+        .args(["-map", "0", "-c", "copy"])
+        .arg(output)
+        .output()
+        .await;
+    let _ = fs::remove_file(&list).await;
+
+    let result = result.context("Failed to run ffmpeg")?;
+    if !result.status.success() {
+        anyhow::bail!(
+            "ffmpeg exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+// Renames `from` to `to` unless `to` exists. A hard link cannot replace a
+// file, so it is tried first; filesystems without them fall back to a check
+// before the rename.
+async fn move_new(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::hard_link(from, to).await {
+        Ok(()) => return fs::remove_file(from).await,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Err(e),
+        Err(_) => {}
+    }
+    if fs::try_exists(to).await? {
+        return Err(std::io::ErrorKind::AlreadyExists.into());
+    }
+    fs::rename(from, to).await
+}
+
+/// Turns the recorded `segments` into `output`: a single segment is renamed,
+/// several are joined and then removed. Missing or empty segments, e.g. from
+/// a wf-recorder that failed to start, are skipped.
+///
+/// The segments are left in place when joining fails, so nothing recorded is
+/// lost.
+pub async fn finalize(segments: &[PathBuf], output: &Path) -> Result<()> {
+    let mut recorded = Vec::new();
+    for segment in segments {
+        if fs::metadata(segment)
+            .await
+            .is_ok_and(|metadata| metadata.len() > 0)
+        {
+            recorded.push(segment.clone());
+        }
+    }
+
+    match recorded.as_slice() {
+        [] => Ok(()),
+        [segment] => move_new(segment, output)
+            .await
+            .with_context(|| format!("Failed to move {} into place", segment.display())),
+        _ => {
+            concat(&recorded, output).await.with_context(|| {
+                format!(
+                    "Failed to join the recorded segments, they were kept in {}",
+                    output.parent().unwrap_or(output).display()
+                )
+            })?;
+            for segment in &recorded {
+                let _ = fs::remove_file(segment).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "wf-recorder-gui-segments-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(&dir).await.unwrap();
+        dir
+    }
+
+    #[test]
+    fn segment_names() {
+        let output = Path::new("/videos/recording_part002.mkv");
+        let segment = segment_path(output, 3);
+        assert_eq!(segment, Path::new("/videos/recording_part002.seg003.mkv"));
+        assert_eq!(segment_index(&segment), Some(3));
+        assert_eq!(segment_index(output), None);
+        assert_eq!(segment_index(Path::new("/videos/a.segment.mkv")), None);
+    }
+
+    #[tokio::test]
+    async fn single_segment_is_moved() {
+        let dir = scratch_dir("move").await;
+        let output = dir.join("recording.mkv");
+        let segments = [segment_path(&output, 1), segment_path(&output, 2)];
+        fs::write(&segments[0], "frames").await.unwrap();
+        // A wf-recorder that failed before writing anything
+        fs::write(&segments[1], "").await.unwrap();
+
+        finalize(&segments, &output).await.unwrap();
+        assert_eq!(fs::read_to_string(&output).await.unwrap(), "frames");
+        assert!(!segments[0].exists());
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn existing_output_is_not_overwritten() {
+        let dir = scratch_dir("exists").await;
+        let output = dir.join("recording.mkv");
+        let segment = segment_path(&output, 1);
+        fs::write(&output, "earlier").await.unwrap();
+        fs::write(&segment, "frames").await.unwrap();
+
+        assert!(finalize(std::slice::from_ref(&segment), &output)
+            .await
+            .is_err());
+        assert_eq!(fs::read_to_string(&output).await.unwrap(), "earlier");
+        assert_eq!(fs::read_to_string(&segment).await.unwrap(), "frames");
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn nothing_recorded() {
+        let dir = scratch_dir("empty").await;
+        let output = dir.join("recording.mkv");
+        finalize(&[segment_path(&output, 1)], &output)
+            .await
+            .unwrap();
+        assert!(!output.exists());
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
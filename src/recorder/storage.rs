@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `path` is NUL terminated and `stats` is written by statvfs
+    // before it is read
+    let stats = unsafe {
+        if libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stats.assume_init()
+    };
+    #[allow(clippy::useless_conversion)] // The field types differ between targets
+    Ok(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+/// Source of the free space figures the disk watchdog works with.
+pub trait FreeSpaceProvider: Send {
+    /// Bytes available on the filesystem holding `path`.
+    fn free_space(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// `FreeSpaceProvider` asking the kernel through statvfs(3).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Statvfs;
+
+impl FreeSpaceProvider for Statvfs {
+    fn free_space(&self, path: &Path) -> io::Result<u64> {
+        free_space(path)
+    }
+}
+
+/// How much room is left for a running recording, in free bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiskSpace {
+    #[default]
+    Plenty,
+    /// Below the warning threshold
+    Low(u64),
+    /// Below the stop threshold, the recording has to end while the file can
+    /// still be finalized
+    Exhausted(u64),
+}
+
+/// Compares the free space on the output filesystem against the warning and
+/// stop thresholds while recording.
+#[derive(Debug, Clone)]
+pub struct DiskWatchdog<P: FreeSpaceProvider = Statvfs> {
+    provider: P,
+    warn_below: u64,
+    stop_below: u64,
+}
+
+impl<P: FreeSpaceProvider> DiskWatchdog<P> {
+    pub fn new(provider: P, warn_below: u64, stop_below: u64) -> Self {
+        Self {
+            provider,
+            warn_below,
+            stop_below,
+        }
+    }
+
+    /// Where `dir` stands. Free space that cannot be determined counts as
+    /// plenty, a failing statvfs should not end a recording.
+    pub fn check(&self, dir: &Path) -> DiskSpace {
+        match self.provider.free_space(dir) {
+            Ok(free) if free < self.stop_below => DiskSpace::Exhausted(free),
+            Ok(free) if free < self.warn_below => DiskSpace::Low(free),
+            Ok(_) | Err(_) => DiskSpace::Plenty,
+        }
+    }
+}
+
+/// Checks that recordings can be written to `dir`: it has to exist, accept
+/// new files and have more than `min_free` bytes available.
+pub fn preflight(dir: &Path, min_free: u64) -> Result<()> {
+    if !dir.is_dir() {
+        anyhow::bail!("The save location {} does not exist", dir.display());
+    }
+
+    // Permission bits do not tell about read-only mounts or ACLs, creating a
+    // file does
+    let probe = dir.join(".wf-recorder-gui-write-test");
+    fs::write(&probe, b"")
+        .with_context(|| format!("The save location {} is not writable", dir.display()))?;
+    let _ = fs::remove_file(&probe);
+
+    let free = free_space(dir)
+        .with_context(|| format!("Failed to check the free space in {}", dir.display()))?;
+    if free <= min_free {
+        anyhow::bail!(
+            "Only {} MB free in {}, at least {} MB are needed to record",
+            free / MEGABYTE,
+            dir.display(),
+            min_free / MEGABYTE
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reports the same figure for every path
+    struct Fixed(u64);
+
+    impl FreeSpaceProvider for Fixed {
+        fn free_space(&self, _path: &Path) -> io::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    struct Failing;
+
+    impl FreeSpaceProvider for Failing {
+        fn free_space(&self, _path: &Path) -> io::Result<u64> {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        }
+    }
+
+    fn check(free: u64) -> DiskSpace {
+        DiskWatchdog::new(Fixed(free), 2 * MEGABYTE, MEGABYTE).check(Path::new("/"))
+    }
+
+    #[test]
+    fn plenty_of_space_is_left_alone() {
+        assert_eq!(check(10 * MEGABYTE), DiskSpace::Plenty);
+        assert_eq!(check(2 * MEGABYTE), DiskSpace::Plenty);
+    }
+
+    #[test]
+    fn warns_below_the_warning_threshold() {
+        assert_eq!(check(2 * MEGABYTE - 1), DiskSpace::Low(2 * MEGABYTE - 1));
+        assert_eq!(check(MEGABYTE), DiskSpace::Low(MEGABYTE));
+    }
+
+    #[test]
+    fn stops_below_the_stop_threshold() {
+        assert_eq!(check(MEGABYTE - 1), DiskSpace::Exhausted(MEGABYTE - 1));
+        assert_eq!(check(0), DiskSpace::Exhausted(0));
+    }
+
+    #[test]
+    fn unknown_space_does_not_stop_the_recording() {
+        let watchdog = DiskWatchdog::new(Failing, 2 * MEGABYTE, MEGABYTE);
+        assert_eq!(watchdog.check(Path::new("/")), DiskSpace::Plenty);
+    }
+
+    #[test]
+    fn statvfs_reports_the_temp_dir() {
+        assert!(Statvfs.free_space(&std::env::temp_dir()).is_ok());
+        assert!(Statvfs.free_space(Path::new("/nonexistent/dir")).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use iced::border::Radius;
-use iced::widget::{button, container};
+use iced::widget::{button, container, progress_bar};
 use iced::{Background, Border, Color, Shadow, Vector};
 
 // Modern glass-morphism inspired color palette
@@ -71,7 +71,7 @@ pub mod design {
     pub const MAX_WINDOW_HEIGHT: f32 = 900.0;
 
     // Compact window sizing
-    pub const COMPACT_WINDOW_WIDTH: f32 = 180.0;
+    pub const COMPACT_WINDOW_WIDTH: f32 = 250.0;
     pub const COMPACT_WINDOW_HEIGHT: f32 = 50.0;
     pub const COMPACT_WINDOW_PADDING: f32 = 20.0;
 
@@ -106,6 +106,9 @@ pub mod design {
     pub const BASE_TIMER_TEXT_SIZE: u16 = 18;
     pub const COMPACT_COUNTDOWN_SIZE: u16 = 28;
     pub const COMPACT_ICON_SIZE: u16 = 16;
+    pub const COMPACT_METER_WIDTH: f32 = 24.0;
+    pub const COMPACT_METER_HEIGHT: f32 = 4.0;
+    pub const FREE_SPACE_INPUT_WIDTH: f32 = 90.0;
 
     // Container sizes (responsive)
     pub const BASE_SMALL_SPACE: f32 = 8.0;
@@ -559,3 +562,22 @@ impl container::StyleSheet for RecordingIndicator {
         }
     }
 }
+
+// Audio level bar, turns red while the signal clips
+pub struct LevelMeter(pub ColorPalette, pub bool); // bool for clipping state
+
+impl progress_bar::StyleSheet for LevelMeter {
+    type Style = iced::Theme;
+
+    fn appearance(&self, _: &Self::Style) -> progress_bar::Appearance {
+        progress_bar::Appearance {
+            background: Background::Color(self.0.surface_elevated),
+            bar: Background::Color(if self.1 {
+                self.0.danger
+            } else {
+                self.0.primary
+            }),
+            border_radius: Radius::from(design::BORDER_RADIUS_SMALL),
+        }
+    }
+}